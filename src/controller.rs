@@ -3,6 +3,13 @@ use crate::prelude::*;
 pub mod main;
 pub mod battle;
 pub mod merchant;
+pub mod trap;
+pub mod disarm;
+pub mod equip;
+pub mod game_over;
 pub use main::run_main_controller;
 pub use battle::run_battle_controller;
 pub use merchant::run_merchant_controller;
+pub use trap::run_trap_controller;
+pub use disarm::run_disarm_controller;
+pub use game_over::run_game_over_controller;
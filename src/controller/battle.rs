@@ -2,33 +2,35 @@ use crate::prelude::*;
 use crate::gamestate::*;
 use crate::enemy::*;
 use crate::item::*;
-use crate::task;
+use crate::task::ControllerContext;
+
+use std::time::Duration;
+
+
+/// How long an enemy will wait for the player to make a move in a realtime game before attacking anyway
+const ENEMY_PATIENCE: Duration = Duration::from_secs(4);
 
 
 #[derive(Debug, Copy, Clone)]
-enum AttackSeverity {
+pub(super) enum AttackSeverity {
 	Crit,
 	Hit,
 	Miss,
 }
 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum PlayerCommand {
-	Attack, Heal, Flee,
+	Attack, Heal, UsePotion, Flee,
 }
 
 
-async fn run_player_attack(enemy_archetype: EnemyArchetype) {
+async fn run_player_attack(ctx: &ControllerContext, enemy_archetype: EnemyArchetype) {
 	use std::cmp::Ordering;
 	use AttackSeverity::*;
 
-	let mut r = rng();
-
-	let enemy_max_roll = if enemy_archetype.is_boss() { 13 } else { 10 };
-
-	let player_roll = r.gen_range(0, 10);
-	let enemy_roll = r.gen_range(0, enemy_max_roll);
+	let player_roll = ctx.rng().gen_range(0, 10);
+	let enemy_roll = ctx.rng().gen_range(0, enemy_archetype.roll());
 
 	match player_roll.cmp(&enemy_roll) {
 		Ordering::Greater => {
@@ -38,7 +40,7 @@ async fn run_player_attack(enemy_archetype: EnemyArchetype) {
 				_ => Hit,
 			};
 
-			let mut damage = get_executor().hack_game().player.attack();
+			let mut damage = ctx.get_player_stats().await.attack;
 
 			match severity {
 				AttackSeverity::Crit => {
@@ -57,14 +59,14 @@ async fn run_player_attack(enemy_archetype: EnemyArchetype) {
 			}
 
 			let enemy_defense = enemy_archetype.defense();
-			let shield_applied = enemy_defense > 0 && rng().gen_ratio(3, 4);
+			let shield_applied = enemy_defense > 0 && ctx.rng().gen_ratio(3, 4);
 
 			if shield_applied {
 				println!("The {:?} raises it's shield and blocks some of your attack", enemy_archetype);
 				damage -= enemy_defense;
 			}
 
-			task::attack_enemy(damage.max(0)).await;
+			ctx.attack_enemy(damage.max(0)).await;
 		}
 
 		Ordering::Equal => {
@@ -72,27 +74,19 @@ async fn run_player_attack(enemy_archetype: EnemyArchetype) {
 		}
 
 		Ordering::Less => {
-			let severity = if enemy_archetype.is_boss() {
-				match enemy_roll {
-					1 => Miss,
-					11..=13 => Crit,
-					_ => Hit,
-				}
-			} else {
-				match enemy_roll {
-					1 => Miss,
-					9..=10 => Crit,
-					_ => Hit,
-				}
+			let severity = match enemy_roll {
+				1 => Miss,
+				_ if enemy_archetype.is_crit(enemy_roll) => Crit,
+				_ => Hit,
 			};
 
-			run_enemy_attack(enemy_archetype, severity, false).await
+			run_enemy_attack(ctx, enemy_archetype, severity, false).await
 		}
 	}
 }
 
 
-async fn run_enemy_attack(archetype: EnemyArchetype, severity: AttackSeverity, ignore_shield: bool) {
+pub(super) async fn run_enemy_attack(ctx: &ControllerContext, archetype: EnemyArchetype, severity: AttackSeverity, ignore_shield: bool) {
 	let mut damage = archetype.attack();
 
 	match severity {
@@ -111,23 +105,34 @@ async fn run_enemy_attack(archetype: EnemyArchetype, severity: AttackSeverity, i
 		}
 	}
 
-	let player_defense = get_executor().hack_game_mut().player.defense();
-	let shield_applied = player_defense > 0 && rng().gen_ratio(3, 4);
+	let player_defense = ctx.get_player_stats().await.defense;
+	let shield_applied = player_defense > 0 && ctx.rng().gen_ratio(3, 4);
 
 	if shield_applied && !ignore_shield {
 		println!("You raise your shield in time to take some of the blow");
 		damage -= player_defense;
 	}
 
-	task::damage_player(damage.max(0) as u32, HealthModifyReason::Attack).await;
+	ctx.damage_player(damage.max(0) as u32, HealthModifyReason::Attack).await;
 }
 
 
-pub async fn run_battle_controller() {
+/// The enemy gets a swing in while the player is busy doing something other than fighting
+async fn run_enemy_opportunity_attack(ctx: &ControllerContext, archetype: EnemyArchetype) {
+	use AttackSeverity::*;
+
+	let probabilities = if archetype.is_boss() {[2, 2, 6]} else {[3, 4, 6]};
+	let severity = choose_with_weights(&[Crit, Hit, Miss], &probabilities, &mut *ctx.rng());
+
+	run_enemy_attack(ctx, archetype, severity, false).await;
+}
+
+
+pub async fn run_battle_controller(ctx: &ControllerContext) {
 	println!("[battle] enter");
 
-	let loc = get_executor().hack_game().player.location;
-	let archetype = get_executor().hack_game().get_enemy(loc)
+	let loc = ctx.get_player_stats().await.location;
+	let archetype = ctx.get_enemy(loc).await
 		.expect("Tried to start battle with no enemy")
 		.archetype;
 
@@ -141,33 +146,43 @@ pub async fn run_battle_controller() {
 
 	println!("Do you fight or run like a coward?");
 
-	while !get_executor().hack_game().get_enemy(loc).unwrap().is_dead() && !get_executor().hack_game().player.is_dead() {
-		let command = task::get_player_command().await;
+	while !ctx.get_enemy(loc).await.unwrap().is_dead() && !ctx.get_player_stats().await.is_dead() {
+		let command = match ctx.get_player_command_within(ENEMY_PATIENCE).await {
+			Some(command) => command,
+			None => {
+				println!("The {:?} grows tired of waiting and attacks!", archetype);
+				run_enemy_opportunity_attack(ctx, archetype).await;
+				continue
+			}
+		};
 
 		match command.battle().unwrap() {
-			PlayerCommand::Attack => run_player_attack(archetype).await,
+			PlayerCommand::Attack => run_player_attack(ctx, archetype).await,
 
 			PlayerCommand::Heal => {
-				if task::consume_player_item(Item::Food).await {
-					task::heal_player(rng().gen_range(1, 4)).await;
-
-					use AttackSeverity::*;
-
-					let probabilities = if archetype.is_boss() {[2, 2, 6]} else {[3, 4, 6]};
-					let severity = choose_with_weights(&[Crit, Hit, Miss], &probabilities);
-
-					run_enemy_attack(archetype, severity, false).await;
+				if ctx.consume_player_item(Item::Food).await {
+					let amount = ctx.rng().gen_range(1, 4);
+					ctx.heal_player(amount).await;
+					run_enemy_opportunity_attack(ctx, archetype).await;
 
 				} else {
 					println!("You don't have enough food!");
 				}
 			}
 
+			PlayerCommand::UsePotion => {
+				if ctx.consume_potion().await {
+					run_enemy_opportunity_attack(ctx, archetype).await;
+				} else {
+					println!("You don't have any potions!");
+				}
+			}
+
 			PlayerCommand::Flee => {
 				println!("You flee like the coward you are");
 				
-				if rng().gen_ratio(2, 5) {
-					run_enemy_attack(archetype, AttackSeverity::Hit, true).await;
+				if ctx.rng().gen_ratio(2, 5) {
+					run_enemy_attack(ctx, archetype, AttackSeverity::Hit, true).await;
 				}
 
 				break;
@@ -175,9 +190,8 @@ pub async fn run_battle_controller() {
 		}
 	}
 
-	if get_executor().hack_game().get_enemy(loc).unwrap().is_dead() {
-		println!("The strike is fatal! The {:?} is defeated!", archetype);
-		get_executor().hack_game_mut().remove_encounter_at(loc);
+	if ctx.get_enemy(loc).await.unwrap().is_dead() {
+		ctx.remove_encounter(loc).await;
 	}
 
 	println!("[battle] leave");
@@ -0,0 +1,78 @@
+use crate::prelude::*;
+use crate::controller::trap::spring_trap;
+use crate::task::ControllerContext;
+
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum PlayerCommand {
+	Attempt, Inspect, Leave,
+}
+
+
+#[derive(Debug, Copy, Clone)]
+enum DisarmOutcome {
+	Success,
+	Failure,
+	Trigger,
+}
+
+
+pub async fn run_disarm_controller(ctx: &ControllerContext) {
+	println!("[disarm] enter");
+
+	let loc = ctx.get_player_stats().await.location;
+	let kind = ctx.get_trap(loc).await
+		.expect("Tried to disarm a trap that doesn't exist");
+
+	assert!(kind.can_disarm(), "{:?} traps can't be disarmed", kind);
+
+	println!("The {:?} trap is still primed. You could try to disarm it", kind);
+
+	let mut inspected = false;
+
+	loop {
+		match *ctx.get_player_command().await.disarm().unwrap() {
+			PlayerCommand::Attempt => {
+				use DisarmOutcome::*;
+
+				// A closer look makes it much harder to get wrong
+				let weights = if inspected { [6, 3, 1] } else { [2, 2, 1] };
+				let outcome = choose_with_weights(&[Success, Failure, Trigger], &weights, &mut *ctx.rng());
+
+				match outcome {
+					Success => {
+						println!("With a satisfying click, the {:?} trap falls apart in your hands", kind);
+						ctx.disarm_trap(loc).await;
+						break
+					}
+
+					Failure => {
+						println!("You fiddle with the mechanism but nothing gives");
+					}
+
+					Trigger => {
+						println!("Oops");
+						spring_trap(ctx, kind).await;
+						break
+					}
+				}
+			}
+
+			PlayerCommand::Inspect => {
+				if inspected {
+					println!("You've already had a good look at it");
+				} else {
+					println!("You take a closer look at the {:?} trap and figure out how it works", kind);
+					inspected = true;
+				}
+			}
+
+			PlayerCommand::Leave => {
+				println!("You decide to leave well enough alone");
+				break
+			}
+		}
+	}
+
+	println!("[disarm] leave");
+}
@@ -0,0 +1,59 @@
+use crate::prelude::*;
+use crate::item::{Item, Equipment};
+use crate::task::{self, ControllerContext};
+
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum PlayerCommand {
+	Swap, Keep,
+}
+
+
+/// Gives the player an item, equipping it straight away if it's equipment and there's a free slot for it,
+/// or asking the player what to do if there isn't
+pub async fn pick_up_item(ctx: &ControllerContext, item: Item) {
+	ctx.give_player_item(item).await;
+
+	let equipment = match item {
+		Item::Equipment(e) => e,
+		_ => return,
+	};
+
+	let current = ctx.get_player().await.equipped(equipment.slot());
+
+	match current {
+		None => {}
+		Some(current) if current == equipment => {
+			println!("You already have a {:?} equipped, so you stash this one in your pack", current);
+			return;
+		}
+
+		Some(current) => {
+			ctx.enter_mode(task::ControllerMode::Equip).await;
+			run_equip_controller(ctx, equipment, current).await;
+			ctx.leave_mode().await;
+			return;
+		}
+	}
+
+	ctx.equip_item(equipment).await;
+}
+
+
+async fn run_equip_controller(ctx: &ControllerContext, equipment: Equipment, current: Equipment) {
+	println!("[equip] enter");
+
+	println!("You already have a {:?} equipped. Do you swap it for the {:?}?", current, equipment);
+
+	match *ctx.get_player_command().await.equip().unwrap() {
+		PlayerCommand::Swap => {
+			ctx.equip_item(equipment).await;
+		}
+
+		PlayerCommand::Keep => {
+			println!("You stash the {:?} in your pack", equipment);
+		}
+	}
+
+	println!("[equip] leave");
+}
@@ -0,0 +1,39 @@
+use crate::prelude::*;
+use crate::gamestate::GameOutcome;
+use crate::task::ControllerContext;
+
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum PlayerCommand {
+	Restart, Quit,
+}
+
+
+/// Shows the player how they did, and returns whether they want to play again
+pub async fn run_game_over_controller(ctx: &ControllerContext) -> bool {
+	println!("[game over] enter");
+
+	let summary = ctx.get_summary().await;
+
+	match summary.outcome {
+		GameOutcome::Victory => println!("You made it out alive!"),
+		GameOutcome::Defeat => println!("Your adventure ends here"),
+	}
+
+	let score = crate::score::calculate(&summary);
+	let rank = ctx.record_score(summary);
+
+	ctx.show_game_over(summary, score, rank).await;
+	ctx.end_game().await;
+
+	println!("Do you want to restart or quit?");
+
+	let restart = match ctx.get_player_command().await.game_over().unwrap() {
+		PlayerCommand::Restart => true,
+		PlayerCommand::Quit => false,
+	};
+
+	println!("[game over] leave");
+
+	restart
+}
@@ -2,10 +2,10 @@ use crate::prelude::*;
 use crate::controller::*;
 use crate::item::*;
 use crate::room::EncounterType;
-use crate::task;
+use crate::task::{self, ControllerContext};
 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum PlayerCommand {
 	GoNorth,
 	GoEast,
@@ -13,91 +13,157 @@ pub enum PlayerCommand {
 	GoWest,
 
 	Heal,
+	UsePotion,
 	Interact,
 
+	Equip(Equipment),
+	Unequip(EquipSlot),
+
+	/// Only takes off that exact piece of equipment, rather than whatever happens to be in its slot
+	UnequipItem(Equipment),
+
+	/// Walks to somewhere the player has already seen, a room at a time
+	GoTo(Destination),
+
 	ShowMap,
 	ShowInventory,
+
+	Save,
+	Load,
+	Restart,
+	Quit,
+}
+
+
+/// Somewhere the player can ask to be taken to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Destination {
+	Location(Location),
+	Exit,
+
+	/// Whichever known merchant is the shortest walk away
+	Merchant,
+}
+
+
+/// How a single game came to an end
+#[derive(Debug, Copy, Clone)]
+enum GameEnd {
+	/// The player either died or escaped
+	Over,
+	Restart,
 	Quit,
 }
 
 
 
-async fn try_move(dir: Direction) {
-	if !task::move_player(dir).await {
+/// Returns whether the player made it into the next room without anything happening that they'd want to stop for
+async fn try_move(ctx: &ControllerContext, dir: Direction) -> bool {
+	if !ctx.move_player(dir).await {
 		println!("You can't go that way");
-		return;
+		return false;
 	}
 
-	if !task::consume_player_item(Item::Food).await {
-		if !task::starve_player().await {
-			println!("You starve to death");
-			return;
+	let mut uneventful = true;
 
-		} else {
-			println!("You have run out of food! You can travel {} rooms",
-				get_executor().hack_game_mut().player.hunger);
+	if !ctx.consume_player_item(Item::Food).await {
+		if !ctx.starve_player().await {
+			return false;
 		}
+
+		uneventful = false;
 	} else {
-		task::sate_player().await;
+		ctx.sate_player().await;
 	}
 
-	let player_pos = get_executor().hack_game().player.location;
-	let current_room = get_executor().hack_game().map.get(player_pos).unwrap();
+	let encountered = enter_room(ctx).await;
+	uneventful && !encountered
+}
 
-	get_executor().hack_game_mut().map.mark_visited(player_pos);
+/// Returns whether anything was encountered on the way in
+async fn enter_room(ctx: &ControllerContext) -> bool {
+	let mut encountered = false;
 
-	// TODO: leaving should be optional
-	if current_room.is_exit {
-		println!("You found the exit!");
-		return;
-	}
+	loop {
+		let player_pos = ctx.get_player_stats().await.location;
+		let current_room = ctx.get_room(player_pos).await.unwrap();
+
+		ctx.mark_visited(player_pos).await;
+
+		if current_room.is_exit && current_room.encounter.is_some() {
+			println!("You found the exit, but something is guarding it!");
+		}
+
+		if let Some(encounter_ty) = current_room.encounter {
+			// Merchants and chests just wait for the player to interact with them
+			encountered |= !matches!(encounter_ty, EncounterType::Merchant | EncounterType::Chest);
+			run_encounter(ctx, encounter_ty).await;
 
-	if let Some(encounter_ty) = current_room.encounter {
-		run_encounter(encounter_ty).await;
+			if !encounter_ty.is_persistent() {
+				ctx.remove_encounter(player_pos).await;
+			}
+		}
+
+		let current_room = ctx.get_room(player_pos).await.unwrap();
+		if current_room.is_exit && current_room.encounter.is_none() {
+			println!("You found the exit!");
+		}
 
-		if !encounter_ty.is_persistent() {
-			get_executor().hack_game_mut().remove_encounter_at(player_pos);
+		// Encounters can send the player somewhere else entirely, in which case
+		// there's a whole new room to deal with
+		let player = ctx.get_player_stats().await;
+		if player.location == player_pos || player.is_dead() {
+			break;
 		}
 	}
 
-	task::show_map(false).await;
+	ctx.show_map(false).await;
+	encountered
 }
 
-async fn run_encounter(encounter_ty: EncounterType) {
+async fn run_encounter(ctx: &ControllerContext, encounter_ty: EncounterType) {
 	println!("]]] running encounter {:?}", encounter_ty);
 
 	match encounter_ty {
-		EncounterType::Food => task::give_player_item(Item::Food).await,
-		EncounterType::Treasure => task::give_player_item(Item::Treasure).await,
-		EncounterType::Key => task::give_player_item(Item::Key).await,
-		EncounterType::Map => task::give_player_item(Item::Map).await,
-
-		EncounterType::Equipment => task::give_player_item(Item::Equipment(random())).await,
+		EncounterType::Food => ctx.give_player_item(Item::Food).await,
+		EncounterType::Treasure => ctx.give_player_item(Item::Treasure).await,
+		EncounterType::Key => ctx.give_player_item(Item::Key).await,
+		EncounterType::Map => ctx.give_player_item(Item::Map).await,
+
+		EncounterType::Equipment => {
+			let equipment = ctx.catalog().choose_equipment(&mut *ctx.rng());
+			equip::pick_up_item(ctx, Item::Equipment(equipment)).await
+		}
 
 		EncounterType::Monster => {
-			let player_loc = get_executor().hack_game().player.location;
-			if get_executor().hack_game_mut().get_enemy(player_loc).is_none() {
-				get_executor().hack_game_mut().spawn_enemy_at(player_loc, false);
+			let player_loc = ctx.get_player_stats().await.location;
+			if ctx.get_enemy(player_loc).await.is_none() {
+				ctx.spawn_enemy(player_loc, false).await;
 			}
 
-			task::enter_mode(task::ControllerMode::Battle).await;
-			run_battle_controller().await;
-			task::leave_mode().await;
+			ctx.enter_mode(task::ControllerMode::Battle).await;
+			run_battle_controller(ctx).await;
+			ctx.leave_mode().await;
 		}
 
 		EncounterType::Boss => {
-			let player_loc = get_executor().hack_game().player.location;
-			if get_executor().hack_game().get_enemy(player_loc).is_none() {
-				get_executor().hack_game_mut().spawn_enemy_at(player_loc, true);
+			let player_loc = ctx.get_player_stats().await.location;
+			if ctx.get_enemy(player_loc).await.is_none() {
+				ctx.spawn_enemy(player_loc, true).await;
 			}
 
-			task::enter_mode(task::ControllerMode::Battle).await;
-			run_battle_controller().await;
-			task::leave_mode().await;
+			ctx.enter_mode(task::ControllerMode::Battle).await;
+			run_battle_controller(ctx).await;
+			ctx.leave_mode().await;
 		}
 
 		EncounterType::Trap => {
-			println!("Do trap");
+			let player_loc = ctx.get_player_stats().await.location;
+			if ctx.get_trap(player_loc).await.is_none() {
+				ctx.spawn_trap(player_loc).await;
+			}
+
+			run_trap_controller(ctx).await;
 		}
 
 		_ => {}
@@ -105,38 +171,40 @@ async fn run_encounter(encounter_ty: EncounterType) {
 }
 
 
-async fn interact() -> bool {
+async fn interact(ctx: &ControllerContext) {
 	use crate::room::EncounterType;
 	
-	let location = get_executor().hack_game().player.location;
-	let room = get_executor().hack_game().map.get(location).unwrap();
+	let location = ctx.get_player_stats().await.location;
+	let room = ctx.get_room(location).await.unwrap();
 
 	if room.is_exit {
-		return true;
+		if let Some(EncounterType::Boss) = room.encounter {
+			println!("The way out is still blocked");
+			run_encounter(ctx, EncounterType::Boss).await;
+		} else {
+			ctx.escape_dungeon().await;
+		}
+
+		return;
 	}
 
 	match room.encounter {
 		Some(EncounterType::Merchant) => {
-			task::enter_mode(task::ControllerMode::Merchant).await;
-			run_merchant_controller().await;
-			task::leave_mode().await;
+			ctx.enter_mode(task::ControllerMode::Merchant).await;
+			run_merchant_controller(ctx).await;
+			ctx.leave_mode().await;
 		}
 
 		Some(EncounterType::Chest) => {
-			let chest_items = [
-				Item::Food, Item::Treasure, Item::Key,
-				Item::Potion, Item::Equipment(random())
-			];
-
-			if task::consume_player_item(Item::Key).await {
-				let num_items = rng().gen_range(1, 5);
-				let items = chest_items.choose_multiple(&mut rng(), num_items);
+			if ctx.consume_player_item(Item::Key).await {
+				let num_items = ctx.rng().gen_range(1, 5);
+				let items = ctx.catalog().choose_loot(num_items, &mut *ctx.rng());
 
 				println!("You found a chest!");
 				println!("You open it with one of your keys");
 
 				for item in items {
-					task::give_player_item(*item).await;
+					equip::pick_up_item(ctx, item).await;
 				}
 
 			} else {
@@ -146,125 +214,297 @@ async fn interact() -> bool {
 
 		_ => {}
 	}
+}
+
+
+/// Walks the player along the shortest route to `destination` through rooms they know about, stopping as soon as
+/// anything happens along the way
+async fn travel(ctx: &ControllerContext, destination: Destination) {
+	let map = ctx.get_map().await;
+	let player = ctx.get_player().await;
+	let has_map = player.inventory.has(Item::Map);
+
+	let route = match destination {
+		Destination::Location(location) => {
+			if !map.is_known(location, has_map) {
+				println!("You don't know what's at {}, {}", location.0, location.1);
+				return;
+			}
+
+			map.find_route(player.location, location, has_map)
+		}
+
+		Destination::Exit => {
+			let exit = map.iter()
+				.find(|&(loc, room)| room.is_exit && map.is_known(loc, has_map));
+
+			match exit {
+				Some((exit, _)) => map.find_route(player.location, exit, has_map),
+				None => {
+					println!("You don't know where the exit is");
+					return;
+				}
+			}
+		}
 
-	false
+		Destination::Merchant => {
+			let merchants: Vec<Location> = map.iter()
+				.filter(|&(loc, room)| matches!(room.encounter, Some(EncounterType::Merchant)) && map.visited(loc))
+				.map(|(loc, _)| loc)
+				.collect();
+
+			if merchants.is_empty() {
+				println!("You don't know of any merchants");
+				return;
+			}
+
+			merchants.into_iter()
+				.filter_map(|loc| map.find_route(player.location, loc, has_map))
+				.min_by_key(Vec::len)
+		}
+	};
+
+	let route = match route {
+		Some(route) => route,
+		None => {
+			println!("You don't know a safe way to get there");
+			return;
+		}
+	};
+
+	if route.len() < 2 {
+		println!("You're already there");
+		return;
+	}
+
+	ctx.show_route(route.clone()).await;
+
+	for step in route.windows(2) {
+		let dir = step[0].direction_to(step[1]).unwrap();
+
+		if !try_move(ctx, dir).await {
+			break
+		}
+	}
 }
 
 
-pub async fn run_main_controller() {
-	println!("[main] enter");
+async fn run_debug_command(ctx: &ControllerContext, command: &[String]) {
+	use crate::room::Room;
 
-	task::enter_mode(task::ControllerMode::Main).await;
+	let player = ctx.get_player().await;
+	let ply_loc = player.location;
+	let room = ctx.get_room(ply_loc).await.unwrap();
 
-	// TODO: this doesn't make sense for a retained mode view
-	task::show_map(false).await;
+	let command: Vec<&str> = command.iter().map(String::as_ref).collect();
 
-	'main_loop: while !get_executor().hack_game().player.is_dead() {
-		// TODO: this should be moved to view, when input is requested
-		println!("Which way do you go?");
+	match &command[..] {
+		["state"] => println!("{}", ctx.describe_state().await),
+		["ply"] => {
+			println!("{:#?}", player);
+			println!("attack {:#?}", player.attack());
+			println!("defense {:#?}", player.defense());
+		}
+		["inv"] => println!("{:#?}", player.inventory),
 
-		loop {
-			let command = task::get_player_command().await;
+		["room"] => println!("{:#?}", room),
+		["validate"] => {
+			let problems = ctx.get_map().await.validate();
+			if problems.is_empty() {
+				println!("Nothing wrong with the map");
+			}
 
-			if let Some(command) = command.debug() {
-				let executor = get_executor();
-				let mut state = executor.hack_game_mut();
-				use crate::room::Room;
+			for problem in problems {
+				println!("{}", problem);
+			}
+		}
 
-				let ply_loc = state.player.location;
-				let room = state.map.get(ply_loc).unwrap();
+		["enemy"] => println!("{:#?}", ctx.get_enemy(ply_loc).await),
 
-				let command: Vec<&str> = command.iter().map(String::as_ref).collect();
+		["g", "health", n] => ctx.heal_player(n.parse().unwrap()).await,
 
-				match &command[..] {
-					["state"] => println!("{:#?}", state),
-					["ply"] => {
-						println!("{:#?}", state.player);
-						println!("attack {:#?}", state.player.attack());
-						println!("defense {:#?}", state.player.defense());
-					}
-					["inv"] => println!("{:#?}", state.player.inventory),
-
-					["room"] => println!("{:#?}", state.map.get(state.player.location)),
-					["enemy"] => println!("{:#?}", state.get_enemy(state.player.location)),
-
-					["g", "key"] => state.player.inventory.add(Item::Key),
-					["g", "key", n] => state.player.inventory.add_n(Item::Key, n.parse().unwrap()),
-					["g", "food"] => state.player.inventory.add(Item::Food),
-					["g", "food", n] => state.player.inventory.add_n(Item::Food, n.parse().unwrap()),
-					["g", "map"] => state.player.inventory.add(Item::Map),
-					["g", "treasure"] => state.player.inventory.add(Item::Treasure),
-					["g", "treasure", n] => state.player.inventory.add_n(Item::Treasure, n.parse().unwrap()),
-
-					["g", "health", n] => { state.player.health += n.parse::<i32>().unwrap() }
-
-					["p", "chest"] => {
-						state.map.replace(ply_loc, Room {
-							encounter: Some(EncounterType::Chest),
-							.. room
-						})
-					}
+		["g", name] | ["g", name, _] => {
+			let n = command.get(2).map_or(1, |n| n.parse().unwrap());
 
-					["p", "exit"] => {
-						state.map.replace(ply_loc, Room {
-							is_exit: true,
-							.. room
-						})
-					}
+			match ctx.catalog().find(name) {
+				Some(item) => ctx.give_player_item_n(item, n).await,
+				None => println!("There's no such item as '{}'", name),
+			}
+		}
+
+		["p", "chest"] => {
+			ctx.replace_room(ply_loc, Room {
+				encounter: Some(EncounterType::Chest),
+				.. room
+			}).await
+		}
 
-					["battle"] => {
-						drop(state);
+		["p", "trap"] => {
+			ctx.replace_room(ply_loc, Room {
+				encounter: Some(EncounterType::Trap),
+				.. room
+			}).await;
 
-						let loc = get_executor().hack_game_mut().player.location;
-						get_executor().hack_game_mut().spawn_enemy_at(loc, random());
+			ctx.spawn_trap(ply_loc).await;
+		}
 
-						task::enter_mode(task::ControllerMode::Battle).await;
-						run_battle_controller().await;
-						task::leave_mode().await;
-					}
+		["p", "exit"] => {
+			ctx.replace_room(ply_loc, Room {
+				is_exit: true,
+				.. room
+			}).await
+		}
 
-					["merchant"] => {
-						drop(state);
+		["p", "exit", "boss"] => {
+			ctx.replace_room(ply_loc, Room {
+				encounter: Some(EncounterType::Boss),
+				is_exit: true,
+				.. room
+			}).await
+		}
 
-						task::enter_mode(task::ControllerMode::Merchant).await;
-						run_merchant_controller().await;
-						task::leave_mode().await;
-					}
+		["battle"] => {
+			let boss = ctx.rng().gen();
+			ctx.spawn_enemy(ply_loc, boss).await;
 
-					_ => {
-						println!("Nani!?");
-					}
+			ctx.enter_mode(task::ControllerMode::Battle).await;
+			run_battle_controller(ctx).await;
+			ctx.leave_mode().await;
+		}
+
+		["trap"] => {
+			ctx.spawn_trap(ply_loc).await;
+			run_trap_controller(ctx).await;
+			ctx.remove_trap(ply_loc).await;
+		}
+
+		["merchant"] => {
+			ctx.enter_mode(task::ControllerMode::Merchant).await;
+			run_merchant_controller(ctx).await;
+			ctx.leave_mode().await;
+		}
+
+		_ => {
+			println!("Nani!?");
+		}
+	}
+}
+
+
+pub async fn run_main_controller(ctx: ControllerContext) {
+	let ctx = &ctx;
+
+	println!("[main] enter");
+
+	ctx.enter_mode(task::ControllerMode::Main).await;
+
+	loop {
+		match run_game(ctx).await {
+			GameEnd::Over => {
+				ctx.enter_mode(task::ControllerMode::GameOver).await;
+				let restart = run_game_over_controller(ctx).await;
+				ctx.leave_mode().await;
+
+				if !restart {
+					break
+				}
+			}
+
+			GameEnd::Restart => {}
+			GameEnd::Quit => break,
+		}
+
+		ctx.restart_game().await;
+	}
+
+	ctx.leave_mode().await;
+
+	println!("[main] leave");
+}
+
+
+async fn run_game(ctx: &ControllerContext) -> GameEnd {
+	// TODO: this doesn't make sense for a retained mode view
+	ctx.show_map(false).await;
+
+	while !ctx.get_player_stats().await.is_dead() && !ctx.has_escaped().await {
+		// TODO: this should be moved to view, when input is requested
+		println!("Which way do you go?");
+
+		loop {
+			let command = ctx.get_player_command().await;
+
+			if let Some(command) = command.debug() {
+				run_debug_command(ctx, command).await;
+
+				// Debug fights and traps can end the game just as well as the real thing
+				let player = ctx.get_player_stats().await;
+				if player.is_dead() || ctx.has_escaped().await {
+					break
 				}
 
 				continue
 			}
 
 			match command.main().unwrap() {
-				PlayerCommand::GoNorth => try_move(Direction::North).await,
-				PlayerCommand::GoEast => try_move(Direction::East).await,
-				PlayerCommand::GoSouth => try_move(Direction::South).await,
-				PlayerCommand::GoWest => try_move(Direction::West).await,
-				PlayerCommand::ShowMap => task::show_map(true).await,
-				PlayerCommand::ShowInventory => task::show_inventory().await,
+				PlayerCommand::GoNorth => { try_move(ctx, Direction::North).await; }
+				PlayerCommand::GoEast => { try_move(ctx, Direction::East).await; }
+				PlayerCommand::GoSouth => { try_move(ctx, Direction::South).await; }
+				PlayerCommand::GoWest => { try_move(ctx, Direction::West).await; }
+				PlayerCommand::ShowMap => ctx.show_map(true).await,
+				PlayerCommand::ShowInventory => ctx.show_inventory().await,
 
 				PlayerCommand::Heal => {
-					if task::consume_player_item(Item::Food).await {
-						task::heal_player(rng().gen_range(1, 4)).await;
+					if ctx.consume_player_item(Item::Food).await {
+						let amount = ctx.rng().gen_range(1, 4);
+						ctx.heal_player(amount).await;
 					} else {
 						println!("You don't have enough food!");
 					}
 				}
 
-				PlayerCommand::Interact => if interact().await { break 'main_loop },
+				PlayerCommand::UsePotion => if !ctx.consume_potion().await {
+					println!("You don't have any potions!");
+				}
+
+				PlayerCommand::Interact => interact(ctx).await,
+				PlayerCommand::GoTo(destination) => travel(ctx, *destination).await,
 
-				PlayerCommand::Quit => break 'main_loop,
+				PlayerCommand::Equip(equipment) => if !ctx.equip_item(*equipment).await {
+					println!("You aren't carrying a {:?}", equipment);
+				}
+
+				PlayerCommand::Unequip(slot) => if !ctx.unequip_item(*slot).await {
+					println!("You don't have a {} equipped", slot);
+				}
+
+				PlayerCommand::UnequipItem(equipment) => {
+					let slot = equipment.slot();
+
+					if ctx.get_player().await.equipped(slot) == Some(*equipment) {
+						ctx.unequip_item(slot).await;
+					} else {
+						println!("You don't have a {:?} equipped", equipment);
+					}
+				}
+
+				PlayerCommand::Save => if !ctx.save_game().await {
+					println!("Your progress could not be saved");
+				}
+
+				PlayerCommand::Load => if ctx.load_game().await {
+					ctx.show_map(false).await;
+				} else {
+					println!("The saved game could not be loaded");
+				}
+
+				PlayerCommand::Restart => return GameEnd::Restart,
+				PlayerCommand::Quit => return GameEnd::Quit,
 			}
 
 			break
 		}
 	}
 
-	task::leave_mode().await;
-
-	println!("[main] leave");
+	GameEnd::Over
 }
\ No newline at end of file
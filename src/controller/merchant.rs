@@ -1,49 +1,74 @@
-// use crate::prelude::*;
-use crate::task;
+use crate::prelude::*;
+use crate::task::ControllerContext;
 use crate::item::Item;
+use crate::controller::equip;
 
+use std::time::Duration;
 
-#[derive(Debug, Clone, Copy)]
+
+/// How long the merchant will put up with the player browsing in a realtime game
+const MERCHANT_PATIENCE: Duration = Duration::from_secs(20);
+
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum PlayerCommand {
 	BuyItem(Item),
 	SellItem(Item),
 
+	/// Whichever piece of equipment the merchant picks out
+	BuyAnyEquipment,
+
 	Leave,
 }
 
 
-fn item_cost(item: Item) -> usize {
-	match item {
-		Item::Food => 1,
-		Item::Map => 3,
-		Item::Key => 5,
-		Item::Potion => 5,
+pub async fn run_merchant_controller(ctx: &ControllerContext) {
+	println!("[merchant] enter");
 
-		Item::Equipment(_) => 5,
+	println!("The merchant greets you");
 
-		Item::Treasure => panic!("Treasure has no cost"),
-	}
-}
+	loop {
+		let command = match ctx.get_player_command_within(MERCHANT_PATIENCE).await {
+			Some(command) => command,
+			None => {
+				println!("The merchant gets sick of you dawdling and shows you the door");
+				break
+			}
+		};
 
+		match *command.merchant().unwrap() {
+			PlayerCommand::BuyItem(item) => buy_item(ctx, item).await,
 
-pub async fn run_merchant_controller() {
-	println!("[merchant] enter");
+			PlayerCommand::BuyAnyEquipment => {
+				let catalog = ctx.catalog();
+				let on_sale: Vec<Item> = catalog.iter_equipment()
+					.map(Item::Equipment)
+					.filter(|&item| catalog.get(item).buy_price.is_some())
+					.collect();
 
-	println!("The merchant greets you");
+				let item = on_sale.choose(&mut *ctx.rng()).cloned();
 
-	loop {
-		match *task::get_player_command().await.merchant().unwrap() {
-			PlayerCommand::BuyItem(item) => {
-				if task::consume_player_item_n(Item::Treasure, item_cost(item)).await {
-					task::give_player_item(item).await
-				} else {
-					println!("You don't have enough treasure!");
+				match item {
+					Some(item) => buy_item(ctx, item).await,
+					None => println!("The merchant doesn't have any equipment to sell you"),
 				}
 			}
 
 			PlayerCommand::SellItem(item) => {
-				if task::consume_player_item(item).await {
-					task::give_player_item_n(Item::Treasure, item_cost(item)).await
+				let price = match ctx.catalog().get(item).sell_price {
+					Some(price) => price,
+					None => {
+						println!("The merchant has no interest in your {:?}", item);
+						continue
+					}
+				};
+
+				// Get rid of anything the player can't use before touching what they can
+				let sold = ctx.consume_player_surplus_item(item).await
+					|| ctx.consume_player_item(item).await;
+
+				if sold {
+					ctx.give_player_item_n(Item::Treasure, price).await
 				} else {
 					println!("Try selling something you actually have lmao");
 				}
@@ -57,4 +82,21 @@ pub async fn run_merchant_controller() {
 	}
 
 	println!("[merchant] leave");
+}
+
+
+async fn buy_item(ctx: &ControllerContext, item: Item) {
+	let price = match ctx.catalog().get(item).buy_price {
+		Some(price) => price,
+		None => {
+			println!("The merchant doesn't have any {:?} to sell you", item);
+			return
+		}
+	};
+
+	if ctx.consume_player_item_n(Item::Treasure, price).await {
+		equip::pick_up_item(ctx, item).await
+	} else {
+		println!("You don't have enough treasure!");
+	}
 }
\ No newline at end of file
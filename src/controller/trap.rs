@@ -0,0 +1,161 @@
+use crate::prelude::*;
+use crate::controller::*;
+use crate::controller::battle::{run_enemy_attack, AttackSeverity};
+use crate::gamestate::HealthModifyReason;
+use crate::room::{Room, EncounterType};
+use crate::item::Item;
+use crate::trap::TrapKind;
+use crate::task::{self, ControllerContext};
+
+
+/// Returns whether the player noticed the tripwire in time to step over it
+async fn run_tripwire(ctx: &ControllerContext) -> bool {
+	if ctx.rng().gen_ratio(1, 3) {
+		println!("You notice a tripwire stretched across the doorway and carefully step over it");
+		return true;
+	}
+
+	trip(ctx).await;
+	false
+}
+
+async fn trip(ctx: &ControllerContext) {
+	println!("You trip over a tripwire and go sprawling!");
+
+	let num_items = ctx.rng().gen_range(1, 3);
+
+	for _ in 0..num_items {
+		let inventory = ctx.get_player().await.inventory;
+
+		let mut items: Vec<Item> = inventory.iter_items().collect();
+		items.extend([Item::Food, Item::Treasure].iter().filter(|&&i| inventory.has(i)));
+
+		let item = items.choose(&mut *ctx.rng()).cloned();
+
+		match item {
+			Some(item) => { ctx.drop_player_item(item).await; }
+			None => break,
+		}
+	}
+}
+
+
+/// Returns whether the bolt missed
+async fn fire_bolt(ctx: &ControllerContext) -> bool {
+	println!("You hear a click, and a bolt flies out of the wall!");
+
+	if ctx.rng().gen_ratio(1, 3) {
+		println!("It whistles past your ear");
+		return true;
+	}
+
+	let damage = ctx.rng().gen_range(1, 3);
+	ctx.damage_player(damage, HealthModifyReason::Trap).await;
+	false
+}
+
+
+#[derive(Debug, Copy, Clone)]
+enum FirstStrike {
+	Enemy,
+	Player,
+	Neither,
+}
+
+
+async fn run_ambush(ctx: &ControllerContext, loc: Location) {
+	// The ambusher hangs around like any other monster until it's dealt with
+	let room = ctx.get_room(loc).await.unwrap();
+	ctx.replace_room(loc, Room { encounter: Some(EncounterType::Monster), .. room }).await;
+	ctx.remove_trap(loc).await;
+
+	if ctx.get_enemy(loc).await.is_none() {
+		ctx.spawn_enemy(loc, false).await;
+	}
+
+	let archetype = ctx.get_enemy(loc).await.unwrap().archetype;
+
+	println!("It's an ambush! A {:?} leaps out of the shadows", archetype);
+
+	use FirstStrike::*;
+	let first_strike = choose_with_weights(&[Enemy, Player, Neither], &[3, 1, 1], &mut *ctx.rng());
+
+	match first_strike {
+		Enemy => {
+			println!("The {:?} catches you off guard", archetype);
+			run_enemy_attack(ctx, archetype, AttackSeverity::Hit, true).await;
+		}
+
+		Player => {
+			println!("You react in time to get a free strike in");
+			let damage = ctx.get_player_stats().await.attack;
+			ctx.attack_enemy(damage).await;
+		}
+
+		Neither => {}
+	}
+
+	if ctx.get_player_stats().await.is_dead() {
+		return;
+	}
+
+	if ctx.get_enemy(loc).await.unwrap().is_dead() {
+		println!("The {:?} falls before it even gets going", archetype);
+		ctx.remove_encounter(loc).await;
+		return;
+	}
+
+	ctx.enter_mode(task::ControllerMode::Battle).await;
+	run_battle_controller(ctx).await;
+	ctx.leave_mode().await;
+}
+
+
+async fn run_portal(ctx: &ControllerContext, loc: Location) {
+	println!("The floor shimmers beneath your feet and the room melts away");
+
+	let destination = ctx.get_map().await.iter()
+		.map(|(room_loc, _)| room_loc)
+		.filter(|&room_loc| room_loc != loc)
+		.choose(&mut *ctx.rng());
+
+	match destination {
+		Some(destination) => ctx.teleport_player(destination).await,
+		None => println!("...and then it comes back. Nothing happens"),
+	}
+}
+
+
+/// Sets off a trap the player is fiddling with, without giving them the chance to notice it first
+pub(super) async fn spring_trap(ctx: &ControllerContext, kind: TrapKind) {
+	match kind {
+		TrapKind::Tripwire => trip(ctx).await,
+		TrapKind::Bolt => { fire_bolt(ctx).await; }
+		TrapKind::Ambush | TrapKind::Portal => unreachable!("{:?} traps can't be sprung by hand", kind),
+	}
+}
+
+
+pub async fn run_trap_controller(ctx: &ControllerContext) {
+	println!("[trap] enter");
+
+	let loc = ctx.get_player_stats().await.location;
+	let kind = ctx.get_trap(loc).await
+		.expect("Tried to spring a trap that doesn't exist");
+
+	let noticed = match kind {
+		TrapKind::Tripwire => run_tripwire(ctx).await,
+		TrapKind::Bolt => fire_bolt(ctx).await,
+		TrapKind::Ambush => { run_ambush(ctx, loc).await; false }
+		TrapKind::Portal => { run_portal(ctx, loc).await; false }
+	};
+
+	// Only a trap the player got away from unscathed is still worth having a go at
+	if noticed {
+		ctx.enter_mode(task::ControllerMode::Disarm).await;
+		run_disarm_controller(ctx).await;
+		ctx.leave_mode().await;
+	}
+
+	println!("[trap] leave");
+}
@@ -0,0 +1,57 @@
+use crate::prelude::*;
+
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+
+/// Definitions the game reads from a data file at startup. They're loaded once and never freed,
+/// so that anything pointing into them can be handed around as freely as any other `Copy` value
+pub trait DataFile: Sized + 'static {
+	/// What's written in the file
+	type Contents: DeserializeOwned;
+
+	/// What the file is called in error messages, e.g. "item catalog"
+	const NAME: &'static str;
+
+	fn from_contents(contents: Self::Contents) -> Self;
+
+	/// Checks for anything that would trip up the game later on, and returns every problem found at once
+	fn validate(&self) -> Vec<String>;
+
+	fn load_from_file(path: impl AsRef<Path>) -> Result<&'static Self, Box<dyn Error>> {
+		let contents = std::fs::read_to_string(path)?;
+		let data = Self::from_contents(ron::de::from_str(&contents)?);
+
+		let problems = data.validate();
+		if !problems.is_empty() {
+			return Err(format!("Invalid {}:\n\t{}", Self::NAME, problems.join("\n\t")).into());
+		}
+
+		Ok(Box::leak(Box::new(data)))
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::room::EncounterTables;
+
+	#[test]
+	fn invalid_files_report_every_problem() {
+		let path = std::env::temp_dir().join("rogue-lewis-encounters.ron");
+		std::fs::write(&path, "[(min_distance: 2, encounter_chance: 1.5, weights: [])]").unwrap();
+
+		let err = EncounterTables::load_from_file(&path).unwrap_err().to_string();
+		std::fs::remove_file(&path).unwrap();
+
+		assert!(err.starts_with("Invalid encounter tables:"), "{}", err);
+		assert!(err.contains("there needs to be one for the start room"), "{}", err);
+		assert!(err.contains("isn't between 0 and 1"), "{}", err);
+	}
+
+	#[test]
+	fn missing_files_are_an_error() {
+		assert!(EncounterTables::load_from_file("data/no-such-file.ron").is_err());
+	}
+}
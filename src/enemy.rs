@@ -1,69 +1,160 @@
 use crate::prelude::*;
 
-#[derive(Debug, Copy, Clone)]
-pub enum EnemyArchetype {
-	Goblin,
-	Ogre,
-	Orc,
-	Gargoyle,
-	Guardian,
-	Minotaur,
+use std::fmt;
+use crate::data::DataFile;
+
+pub const DEFAULT_ENEMIES_PATH: &str = "data/enemies.ron";
+
+
+/// Everything there is to know about a kind of enemy, as described in the enemy definitions file
+#[derive(Debug, Deserialize)]
+pub struct EnemyDefinition {
+	/// Saves refer to archetypes by name, so this has to be a plain identifier
+	pub name: String,
+
+	pub health: i32,
+	pub attack: i32,
+
+	#[serde(default)]
+	pub defense: i32,
+
+	#[serde(default)]
+	pub boss: bool,
+
+	/// How likely this archetype is to be picked over others of the same kind. Zero means never
+	pub spawn_weight: i32,
+
+	/// Enemies roll from zero up to but not including this when trading blows with the player
+	pub roll: i32,
+
+	/// The range of rolls, inclusive, that land a critical hit
+	pub crit: (i32, i32),
+}
+
+
+/// Every kind of enemy there is, as described in the enemy definitions file
+pub struct Bestiary {
+	definitions: Vec<EnemyDefinition>,
 }
 
-#[derive(Debug, Copy, Clone)]
+/// A kind of enemy, and all of its stats
+#[derive(Copy, Clone)]
+pub struct EnemyArchetype(&'static EnemyDefinition);
+
+#[derive(Debug, Copy, Clone, Serialize)]
 pub struct Enemy {
 	pub archetype: EnemyArchetype,
 	pub health: i32,
 }
 
 
-use EnemyArchetype::*;
+impl DataFile for Bestiary {
+	type Contents = Vec<EnemyDefinition>;
+	const NAME: &'static str = "enemy definitions";
 
-impl EnemyArchetype {
-	pub fn choose(boss: bool) -> Self {
-		let options: &[EnemyArchetype] = if boss {
-			&[Guardian, Minotaur]
-		} else {
-			&[Goblin, Ogre, Orc, Gargoyle]
-		};
-
-		*options.choose(&mut rng()).unwrap()
+	fn from_contents(definitions: Vec<EnemyDefinition>) -> Bestiary {
+		Bestiary { definitions }
 	}
 
-	pub fn health(self) -> i32 {
-		match self {
-			Goblin => 3,
-			Ogre => 6,
-			Orc => 5,
-			Gargoyle => 2,
-			
-			Guardian => 15,
-			Minotaur => 9,
+	fn validate(&self) -> Vec<String> {
+		let mut problems = Vec::new();
+
+		for (index, def) in self.definitions.iter().enumerate() {
+			let name = &def.name;
+
+			let is_identifier = name.chars().next().map_or(false, |c| c.is_ascii_alphabetic())
+				&& name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+			if !is_identifier {
+				problems.push(format!("'{}' isn't a valid name, names must be a letter followed by letters, digits or underscores", name));
+			}
+
+			if self.definitions[..index].iter().any(|other| other.name == *name) {
+				problems.push(format!("{} is defined more than once", name));
+			}
+
+			if def.health <= 0 {
+				problems.push(format!("{} must have positive health", name));
+			}
+
+			if def.attack < 0 || def.defense < 0 {
+				problems.push(format!("{} can't have negative attack or defense", name));
+			}
+
+			if def.spawn_weight < 0 {
+				problems.push(format!("{} can't have a negative spawn weight", name));
+			}
+
+			if def.roll < 2 {
+				problems.push(format!("{} must roll at least a 2 sided die", name));
+			}
+
+			let (crit_start, crit_end) = def.crit;
+			if crit_start > crit_end || crit_start < 0 || crit_start >= def.roll {
+				problems.push(format!("{} has a crit range of {}..={} which it can never roll with a {} sided die",
+					name, crit_start, crit_end, def.roll));
+			}
 		}
-	}
 
-	pub fn attack(self) -> i32 {
-		match self {
-			Goblin => 1,
-			Ogre => 1,
-			Orc => 2,
-			Gargoyle => 4,
-			
-			Guardian => 1,
-			Minotaur => 4,
+		for &boss in &[false, true] {
+			let total_weight: i32 = self.definitions.iter()
+				.filter(|def| def.boss == boss)
+				.map(|def| def.spawn_weight.max(0))
+				.sum();
+
+			if total_weight <= 0 {
+				let kind = if boss { "boss" } else { "monster" };
+				problems.push(format!("There needs to be at least one {} with a spawn weight above zero", kind));
+			}
 		}
+
+		problems
 	}
+}
 
-	pub fn defense(self) -> i32 {
-		match self {
-			Guardian => 2,
-			Minotaur => 1,
-			_ => 0
-		}
+impl Bestiary {
+	pub fn choose(&'static self, boss: bool, rng: &mut impl Rng) -> EnemyArchetype {
+		let options: Vec<EnemyArchetype> = self.definitions.iter()
+			.filter(|def| def.boss == boss)
+			.map(EnemyArchetype)
+			.collect();
+
+		let weights: Vec<i32> = options.iter().map(|a| a.0.spawn_weight).collect();
+
+		choose_with_weights(&options, &weights, rng)
 	}
 
-	pub fn is_boss(self) -> bool {
-		matches!(self, Guardian | Minotaur)
+	pub fn find(&'static self, name: &str) -> Option<EnemyArchetype> {
+		self.definitions.iter()
+			.find(|def| def.name == name)
+			.map(EnemyArchetype)
+	}
+}
+
+
+/// Just the names, since the full definitions would swamp anything that prints out the game state
+impl fmt::Debug for Bestiary {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_list()
+			.entries(self.definitions.iter().map(|def| &def.name))
+			.finish()
+	}
+}
+
+
+impl EnemyArchetype {
+	pub fn name(self) -> &'static str { &self.0.name }
+
+	pub fn health(self) -> i32 { self.0.health }
+	pub fn attack(self) -> i32 { self.0.attack }
+	pub fn defense(self) -> i32 { self.0.defense }
+	pub fn is_boss(self) -> bool { self.0.boss }
+
+	pub fn roll(self) -> i32 { self.0.roll }
+
+	pub fn is_crit(self, roll: i32) -> bool {
+		let (start, end) = self.0.crit;
+		(start..=end).contains(&roll)
 	}
 
 	pub fn new(self) -> Enemy {
@@ -71,7 +162,22 @@ impl EnemyArchetype {
 	}
 }
 
+/// Archetypes read just like the enum they used to be in messages
+impl fmt::Debug for EnemyArchetype {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+/// Written out by name, the same way as the enum they used to be. There's no `Deserialize`,
+/// since turning a name back into an archetype needs a `Bestiary` to look it up in
+impl Serialize for EnemyArchetype {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_unit_variant("EnemyArchetype", 0, self.name())
+	}
+}
+
 
 impl Enemy {
 	pub fn is_dead(&self) -> bool { self.health <= 0 }
-}
\ No newline at end of file
+}
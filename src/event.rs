@@ -0,0 +1,97 @@
+use crate::prelude::*;
+use crate::gamestate::HealthModifyReason;
+use crate::item::{Item, Equipment, EquipSlot};
+use crate::enemy::EnemyArchetype;
+
+use std::fs::File;
+use std::io::{Write, BufWriter};
+use std::path::Path;
+
+
+/// Something that happened to the game, as reported by the model once it's happened
+#[derive(Debug, Copy, Clone)]
+pub enum GameEvent {
+	ItemGained { item: Item, count: usize },
+
+	/// Picked up beyond the item's stack limit, so only good for selling
+	SurplusItemGained { item: Item, count: usize },
+	ItemConsumed { item: Item, count: usize },
+	SurplusItemConsumed { item: Item, count: usize },
+	ItemDropped { item: Item, count: usize },
+
+	ItemEquipped(Equipment),
+	ItemUnequipped(EquipSlot),
+
+	PlayerHealed { amount: i32, health: i32 },
+	PlayerDamaged { amount: i32, reason: HealthModifyReason, remaining: i32, fatal: bool },
+	PotionDrunk { health: i32 },
+	PlayerStarved { hunger: i32, fatal: bool },
+	PlayerSated,
+
+	RoomEntered { location: Location, direction: Direction, first_visit: bool },
+	PlayerTeleported { location: Location },
+
+	EnemyDamaged { archetype: EnemyArchetype, amount: i32, remaining: i32 },
+	EnemyKilled { archetype: EnemyArchetype },
+	TrapDisarmed,
+
+	DungeonEscaped,
+
+	GameSaved,
+	GameLoaded,
+	GameEnded,
+	GameRestarted { seed: u64 },
+}
+
+
+pub trait EventSubscriber {
+	fn on_event(&mut self, event: &GameEvent);
+}
+
+impl<F> EventSubscriber for F where F: FnMut(&GameEvent) {
+	fn on_event(&mut self, event: &GameEvent) { self(event) }
+}
+
+
+/// Hands every event to everything that's interested in it, in the order they subscribed.
+/// The view isn't subscribed here, since controllers need to wait on it to show each event
+pub struct EventBus {
+	subscribers: Vec<Box<dyn EventSubscriber>>,
+}
+
+impl EventBus {
+	pub fn new() -> EventBus {
+		EventBus { subscribers: Vec::new() }
+	}
+
+	pub fn subscribe(&mut self, subscriber: impl EventSubscriber + 'static) {
+		self.subscribers.push(Box::new(subscriber));
+	}
+
+	pub fn publish(&mut self, event: &GameEvent) {
+		for subscriber in self.subscribers.iter_mut() {
+			subscriber.on_event(event);
+		}
+	}
+}
+
+
+/// Writes out every event as it happens, one per line. Stops logging if the log can't be written to
+pub struct EventLog {
+	writer: Option<BufWriter<File>>,
+}
+
+impl EventLog {
+	pub fn create(path: impl AsRef<Path>) -> Result<EventLog, Box<dyn Error>> {
+		Ok(EventLog { writer: Some(BufWriter::new(File::create(path)?)) })
+	}
+}
+
+impl EventSubscriber for EventLog {
+	fn on_event(&mut self, event: &GameEvent) {
+		if let Some(Err(err)) = self.writer.as_mut().map(|w| writeln!(w, "{:?}", event)) {
+			eprintln!("Failed to write to event log, logging stopped: {}", err);
+			self.writer = None;
+		}
+	}
+}
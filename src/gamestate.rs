@@ -1,16 +1,20 @@
 use crate::prelude::*;
-use crate::map::{Map, MapBuilder};
-use crate::room::Room;
+use crate::map::{Map, MapBuilder, MapProblem};
+use crate::mapgen::{MapGenerator, GeneratorSettings};
+use crate::room::{Room, EncounterTables};
 use crate::enemy::*;
+use crate::trap::TrapKind;
 use crate::task::UntypedPromise;
 use crate::item::*;
+use crate::event::GameEvent;
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 #[derive(Copy, Clone, Debug)]
 pub enum HealthModifyReason {
 	Heal,
 	Attack,
+	Trap,
 }
 
 
@@ -18,12 +22,59 @@ pub enum HealthModifyReason {
 pub enum GameCommand {
 	GivePlayerItem(Item, usize),
 	ConsumePlayerItem(Item, usize),
+	ConsumePlayerSurplusItem(Item, usize),
 	ModifyPlayerHealth(i32, HealthModifyReason),
+	ConsumePotion,
 	StarvePlayer,
 	SatePlayer,
 	MovePlayer(Direction),
+	TeleportPlayer(Location),
+	DropPlayerItem(Item, usize),
+	EquipItem(Equipment),
+	UnequipItem(EquipSlot),
+	DisarmTrap(Location),
+	EscapeDungeon,
 
 	AttackEnemy(i32),
+
+	SpawnEnemy(Location, bool),
+	SpawnTrap(Location),
+	RemoveTrap(Location),
+	RemoveEncounter(Location),
+	ReplaceRoom(Location, Room),
+	MarkVisited(Location),
+
+	SaveGame,
+	LoadGame,
+
+	/// Lets everyone know how the game went, once it's over
+	EndGame,
+
+	/// Throws away the current game and generates a whole new dungeon to start again in
+	Restart,
+
+	// Queries. These leave the game as it is and just report back on it
+	GetPlayer,
+	GetPlayerStats,
+	GetMap,
+	GetRoom(Location),
+	GetEnemy(Location),
+	GetTrap(Location),
+	HasEscaped,
+	GetSummary,
+	DescribeState,
+}
+
+
+/// Everything loaded or decided on at startup, none of which ever changes
+#[derive(Debug, Copy, Clone)]
+pub struct GameData {
+	pub bestiary: &'static Bestiary,
+	pub catalog: &'static ItemCatalog,
+	pub encounters: &'static EncounterTables,
+
+	/// Used for every new dungeon, including ones started by restarting
+	pub generator: GeneratorSettings,
 }
 
 
@@ -32,27 +83,115 @@ pub struct GameState {
 	pub map: Map,
 	pub player: Player,
 
-	pub enemies: HashMap<Location, Enemy>,
+	pub enemies: BTreeMap<Location, Enemy>,
+	pub traps: BTreeMap<Location, TrapKind>,
+
+	/// Set once the player makes it out through the exit. The game is over and won at this point
+	pub escaped: bool,
+
+	/// Includes bosses
+	pub enemies_killed: usize,
+	pub bosses_killed: usize,
+
+	/// How many rooms the player has moved through
+	pub turns: usize,
+
+	pub seed: u64,
+	pub rng: RngHandle,
+
+	/// Stays the same across restarts and loads
+	pub data: GameData,
+
+	/// Everything that's happened since the last `take_events`
+	events: Vec<GameEvent>,
 }
 
 
 impl GameState {
-	pub fn new() -> GameState {
+	pub fn new(seed: u64, data: GameData) -> GameState {
 		GameState {
 			map: Map::new(),
-			player: Player::new(),
+			player: Player::new(data.catalog),
+
+			enemies: BTreeMap::new(),
+			traps: BTreeMap::new(),
+
+			escaped: false,
+			enemies_killed: 0,
+			bosses_killed: 0,
+			turns: 0,
 
-			enemies: HashMap::new(),
+			seed,
+			rng: Rc::new(RefCell::new(GameRng::seed_from_u64(seed))),
+			data,
+
+			events: Vec::new(),
 		}
 	}
 
+	/// Generates a new map, repairing anything that would stop it from being played through.
+	/// Returns whatever needed repairing
+	pub fn generate_map(&mut self) -> Vec<MapProblem> {
+		let mut rng = self.rng.borrow_mut();
+		let mut builder = MapBuilder::new(&mut self.map, &mut rng, self.data.encounters);
+
+		self.data.generator.generate(&mut builder);
+		builder.place_exit();
+
+		let problems = builder.map().validate();
+		if !problems.is_empty() {
+			builder.repair();
+		}
+
+		problems
+	}
+
+	/// Replaces everything with a brand new game generated from `seed`.
+	/// `rng` is reseeded in place rather than replaced, so existing handles to it remain valid
+	pub fn restart(&mut self, seed: u64) {
+		*self.rng.borrow_mut() = GameRng::seed_from_u64(seed);
+
+		self.map = Map::new();
+		self.player = Player::new(self.data.catalog);
+		self.enemies.clear();
+		self.traps.clear();
+		self.escaped = false;
+		self.enemies_killed = 0;
+		self.bosses_killed = 0;
+		self.turns = 0;
+		self.seed = seed;
+
+		self.generate_map();
+	}
+
+	pub fn summary(&self) -> GameSummary {
+		GameSummary {
+			seed: self.seed,
+			outcome: if self.escaped { GameOutcome::Victory } else { GameOutcome::Defeat },
+			rooms_explored: self.map.visited_count(),
+			enemies_killed: self.enemies_killed,
+			bosses_killed: self.bosses_killed,
+			treasure: self.player.inventory.count(Item::Treasure),
+			turns: self.turns,
+		}
+	}
+
+	fn emit(&mut self, event: GameEvent) {
+		self.events.push(event);
+	}
+
+	pub fn take_events(&mut self) -> Vec<GameEvent> {
+		std::mem::replace(&mut self.events, Vec::new())
+	}
+
 	pub fn try_move_player(&mut self, dir: Direction) -> bool {
 		let room = self.map.get(self.player.location)
 			.expect("Player somehow not in a room");
 
 		if room.door(dir) {
 			self.player.location = self.player.location.offset_in_direction(dir);
-			MapBuilder::new(&mut self.map).generate_room_at(self.player.location);
+			MapBuilder::new(&mut self.map, &mut self.rng.borrow_mut(), self.data.encounters)
+				.generate_room_at(self.player.location);
 			true
 		} else {
 			false
@@ -65,10 +204,11 @@ impl GameState {
 		}
 
 		self.enemies.remove(&loc);
+		self.traps.remove(&loc);
 	}
 
 	pub fn spawn_enemy_at(&mut self, loc: Location, boss: bool) {
-		let archetype = EnemyArchetype::choose(boss);
+		let archetype = self.data.bestiary.choose(boss, &mut *self.rng.borrow_mut());
 		self.enemies.insert(loc, archetype.new());
 	}
 
@@ -80,66 +220,275 @@ impl GameState {
 		self.enemies.insert(loc, enemy);
 	}
 
+	pub fn spawn_trap_at(&mut self, loc: Location) {
+		let kind = self.rng.borrow_mut().gen();
+		self.traps.insert(loc, kind);
+	}
+
+	pub fn get_trap(&self, loc: Location) -> Option<TrapKind> {
+		self.traps.get(&loc).copied()
+	}
+
 	pub fn submit_command(&mut self, event: GameCommand, promise: UntypedPromise) {
 		match event {
 			GameCommand::GivePlayerItem(item, n) => {
-				self.player.inventory.add_n(item, n);
-				promise.void().fulfill(());
+				let surplus = self.player.inventory.add_n(self.data.catalog, item, n);
+
+				let kept = n - surplus;
+				if kept > 0 {
+					self.emit(GameEvent::ItemGained { item, count: kept });
+				}
+
+				if surplus > 0 {
+					self.emit(GameEvent::SurplusItemGained { item, count: surplus });
+				}
+
+				promise.usize().fulfill(surplus);
 			}
 
 			GameCommand::ConsumePlayerItem(item, n) => {
 				let success = self.player.inventory.take_n(item, n);
+				if success {
+					self.emit(GameEvent::ItemConsumed { item, count: n });
+				}
+
+				promise.bool().fulfill(success);
+			}
+
+			GameCommand::ConsumePlayerSurplusItem(item, n) => {
+				let success = self.player.inventory.take_surplus_n(item, n);
+				if success {
+					self.emit(GameEvent::SurplusItemConsumed { item, count: n });
+				}
+
 				promise.bool().fulfill(success);
 			}
 
-			GameCommand::ModifyPlayerHealth(n, _) => {
+			GameCommand::ModifyPlayerHealth(n, reason) => {
 				self.player.health += n;
-				promise.bool().fulfill(self.player.health > 0);
+
+				let health = self.player.health;
+				if n > 0 {
+					self.emit(GameEvent::PlayerHealed { amount: n, health });
+				} else if n < 0 {
+					self.emit(GameEvent::PlayerDamaged { amount: -n, reason, remaining: health, fatal: health <= 0 });
+				}
+
+				promise.bool().fulfill(!self.player.is_dead());
+			}
+
+			GameCommand::ConsumePotion => {
+				let success = self.player.inventory.take(Item::Potion);
+				if success {
+					self.player.health *= 2;
+					self.emit(GameEvent::PotionDrunk { health: self.player.health });
+				}
+
+				promise.bool().fulfill(success);
 			}
 
 			GameCommand::StarvePlayer => {
 				self.player.hunger -= 1;
-				promise.bool().fulfill(self.player.hunger > 0);
+
+				let hunger = self.player.hunger;
+				self.emit(GameEvent::PlayerStarved { hunger, fatal: hunger <= 0 });
+				promise.bool().fulfill(hunger > 0);
 			}
 
 			GameCommand::SatePlayer => {
 				self.player.hunger = 10;
+				self.emit(GameEvent::PlayerSated);
 				promise.void().fulfill(());
 			}
 
 			GameCommand::MovePlayer(dir) => {
-				promise.bool().fulfill(self.try_move_player(dir));
+				let success = self.try_move_player(dir);
+				if success {
+					self.turns += 1;
+
+					// Rooms are only marked as visited once the player has had a look around
+					let location = self.player.location;
+					let first_visit = !self.map.visited(location);
+					self.emit(GameEvent::RoomEntered { location, direction: dir, first_visit });
+				}
+
+				promise.bool().fulfill(success);
+			}
+
+			GameCommand::TeleportPlayer(loc) => {
+				assert!(self.map.has(loc), "Trying to teleport player to a room that doesn't exist");
+				self.player.location = loc;
+				self.emit(GameEvent::PlayerTeleported { location: loc });
+				promise.void().fulfill(());
+			}
+
+			GameCommand::DropPlayerItem(item, n) => {
+				let success = self.player.inventory.take_n(item, n);
+				if success {
+					self.emit(GameEvent::ItemDropped { item, count: n });
+				}
+
+				promise.bool().fulfill(success);
+			}
+
+			GameCommand::EquipItem(equipment) => {
+				let success = self.player.equip(self.data.catalog, equipment);
+				if success {
+					self.emit(GameEvent::ItemEquipped(equipment));
+				}
+
+				promise.bool().fulfill(success);
+			}
+
+			GameCommand::UnequipItem(slot) => {
+				let success = self.player.unequip(self.data.catalog, slot);
+				if success {
+					self.emit(GameEvent::ItemUnequipped(slot));
+				}
+
+				promise.bool().fulfill(success);
+			}
+
+			GameCommand::DisarmTrap(loc) => {
+				assert!(self.traps.contains_key(&loc), "Tried to disarm a trap that doesn't exist");
+				self.remove_encounter_at(loc);
+				self.emit(GameEvent::TrapDisarmed);
+				promise.void().fulfill(());
 			}
 
 			GameCommand::AttackEnemy(dmg) => {
 				let loc = self.player.location;
 				if let Some(mut enemy) = self.get_enemy(loc) {
+					let was_dead = enemy.is_dead();
+
 					enemy.health -= dmg;
 					self.update_enemy(loc, enemy);
+
+					let archetype = enemy.archetype;
+					self.emit(GameEvent::EnemyDamaged { archetype, amount: dmg, remaining: enemy.health });
+
+					if enemy.is_dead() && !was_dead {
+						self.enemies_killed += 1;
+						if archetype.is_boss() {
+							self.bosses_killed += 1;
+						}
+
+						self.emit(GameEvent::EnemyKilled { archetype });
+					}
+				}
+
+				promise.void().fulfill(());
+			}
+
+			GameCommand::EscapeDungeon => {
+				let room = self.map.get(self.player.location).unwrap();
+				assert!(room.is_exit, "Tried to escape from a room that isn't the exit");
+				assert!(self.get_enemy(self.player.location).is_none(), "Tried to escape past a living boss");
+
+				self.escaped = true;
+				self.emit(GameEvent::DungeonEscaped);
+				promise.void().fulfill(());
+			}
+
+			GameCommand::SpawnEnemy(loc, boss) => {
+				self.spawn_enemy_at(loc, boss);
+				promise.void().fulfill(());
+			}
+
+			GameCommand::SpawnTrap(loc) => {
+				self.spawn_trap_at(loc);
+				promise.void().fulfill(());
+			}
+
+			GameCommand::RemoveTrap(loc) => {
+				self.traps.remove(&loc);
+				promise.void().fulfill(());
+			}
+
+			GameCommand::RemoveEncounter(loc) => {
+				self.remove_encounter_at(loc);
+				promise.void().fulfill(());
+			}
+
+			GameCommand::ReplaceRoom(loc, room) => {
+				assert!(self.map.has(loc), "Trying to replace a room that doesn't exist");
+				self.map.replace(loc, room);
+				promise.void().fulfill(());
+			}
+
+			GameCommand::MarkVisited(loc) => {
+				self.map.mark_visited(loc);
+				promise.void().fulfill(());
+			}
+
+			GameCommand::SaveGame => {
+				let result = crate::save::save_to_file(self, crate::save::DEFAULT_SAVE_PATH);
+				if let Err(err) = &result {
+					eprintln!("Failed to save game: {}", err);
+				} else {
+					self.emit(GameEvent::GameSaved);
+				}
+
+				promise.bool().fulfill(result.is_ok());
+			}
+
+			GameCommand::LoadGame => {
+				let result = crate::save::load_from_file(self, crate::save::DEFAULT_SAVE_PATH);
+				if let Err(err) = &result {
+					eprintln!("Failed to load game: {}", err);
+				} else {
+					self.emit(GameEvent::GameLoaded);
 				}
 
+				promise.bool().fulfill(result.is_ok());
+			}
+
+			GameCommand::EndGame => {
+				self.emit(GameEvent::GameEnded);
 				promise.void().fulfill(());
 			}
+
+			GameCommand::Restart => {
+				// Drawn from the current game, so that a replay restarts into the same dungeon
+				let seed = self.rng.borrow_mut().gen();
+				self.restart(seed);
+				self.emit(GameEvent::GameRestarted { seed });
+				promise.void().fulfill(());
+			}
+
+			GameCommand::GetPlayer => promise.typed().fulfill(self.player.clone()),
+			GameCommand::GetPlayerStats => promise.typed().fulfill(self.player.stats()),
+			GameCommand::GetMap => promise.typed().fulfill(self.map.clone()),
+			GameCommand::GetRoom(loc) => promise.typed().fulfill(self.map.get(loc)),
+			GameCommand::GetEnemy(loc) => promise.typed().fulfill(self.get_enemy(loc)),
+			GameCommand::GetTrap(loc) => promise.typed().fulfill(self.get_trap(loc)),
+			GameCommand::HasEscaped => promise.bool().fulfill(self.escaped),
+			GameCommand::GetSummary => promise.typed().fulfill(self.summary()),
+			GameCommand::DescribeState => promise.string().fulfill(format!("{:#?}", self)),
 		}
 	}
 }
 
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
 	pub location: Location,
 	pub health: i32,
 	pub hunger: i32,
 
 	pub inventory: Inventory,
+
+	/// Indexed by `EquipSlot`. Equipped items are taken out of `inventory` while they're worn
+	#[serde(default)]
+	pub equipped: [Option<Equipment>; 3],
 }
 
 impl Player {
-	pub fn new() -> Self {
+	pub fn new(catalog: &ItemCatalog) -> Self {
 		let mut inventory = Inventory::new();
-		inventory.add_n(Item::Food, 20);
-		inventory.add_n(Item::Treasure, 5);
+		inventory.add_n(catalog, Item::Food, 20);
+		inventory.add_n(catalog, Item::Treasure, 5);
 
 		Player {
 			location: Location(0, 0),
@@ -147,28 +496,105 @@ impl Player {
 			hunger: 10,
 
 			inventory,
+			equipped: [None; 3],
 		}
 	}
 
 	pub fn attack(&self) -> i32 {
-		let weapon_stat: i32 = self.inventory.iter_equipment()
-			.map(Equipment::attack_bonus).sum();
-
+		let weapon_stat: i32 = self.iter_equipped().map(Equipment::attack).sum();
 		2 + weapon_stat
 	}
 	pub fn defense(&self) -> i32 {
-		self.inventory.iter_equipment().map(Equipment::defense_bonus).sum()
+		self.iter_equipped().map(Equipment::defense).sum()
+	}
+
+	pub fn equipped(&self, slot: EquipSlot) -> Option<Equipment> { self.equipped[slot as usize] }
+
+	pub fn iter_equipped(&self) -> impl Iterator<Item=Equipment> + '_ {
+		self.equipped.iter().filter_map(|&e| e)
+	}
+
+	/// Moves `equipment` from the inventory into its slot, putting whatever was there before back
+	/// into the inventory. Fails if the player isn't carrying `equipment`
+	pub fn equip(&mut self, catalog: &ItemCatalog, equipment: Equipment) -> bool {
+		if !self.inventory.take(Item::Equipment(equipment)) {
+			return false
+		}
+
+		if let Some(prev) = self.equipped[equipment.slot() as usize].replace(equipment) {
+			self.inventory.add(catalog, Item::Equipment(prev));
+		}
+
+		true
+	}
+
+	/// Moves whatever is in `slot` back into the inventory. Fails if there's nothing there
+	pub fn unequip(&mut self, catalog: &ItemCatalog, slot: EquipSlot) -> bool {
+		match self.equipped[slot as usize].take() {
+			Some(prev) => {
+				self.inventory.add(catalog, Item::Equipment(prev));
+				true
+			}
+
+			None => false
+		}
 	}
 
 	pub fn is_dead(&self) -> bool { self.health <= 0 }
+
+	pub fn stats(&self) -> PlayerStats {
+		PlayerStats {
+			location: self.location,
+			health: self.health,
+			attack: self.attack(),
+			defense: self.defense(),
+		}
+	}
 }
 
 
-#[derive(Debug)]
+/// Everything about the player that matters in a fight, and where they are. Hunger is reported by `GameEvent::PlayerStarved`
+#[derive(Debug, Copy, Clone)]
+pub struct PlayerStats {
+	pub location: Location,
+	pub health: i32,
+	pub attack: i32,
+	pub defense: i32,
+}
+
+impl PlayerStats {
+	pub fn is_dead(&self) -> bool { self.health <= 0 }
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameOutcome {
+	Victory,
+	Defeat,
+}
+
+/// How a game went, for once it's over
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct GameSummary {
+	pub seed: u64,
+	pub outcome: GameOutcome,
+	pub rooms_explored: usize,
+	pub enemies_killed: usize,
+	pub bosses_killed: usize,
+	pub treasure: usize,
+	pub turns: usize,
+}
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Inventory {
 	items: Vec<Item>,
 	treasure: i32,
 	food: i32,
+
+	/// Items picked up beyond their stack limit. They're no use to the player, but they can still be sold
+	#[serde(default)]
+	surplus: Vec<Item>,
 }
 
 impl Inventory {
@@ -177,20 +603,40 @@ impl Inventory {
 			items: Vec::new(),
 			treasure: 0,
 			food: 0,
+			surplus: Vec::new(),
 		}
 	}
 
-	pub fn add(&mut self, item: Item) { self.add_n(item, 1) }
+	pub fn add(&mut self, catalog: &ItemCatalog, item: Item) -> usize { self.add_n(catalog, item, 1) }
+
+	/// Adds as many of `item` as its stack limit in `catalog` allows, and stashes the rest as surplus.
+	/// Returns how many ended up as surplus
+	pub fn add_n(&mut self, catalog: &ItemCatalog, item: Item, n: usize) -> usize {
+		let space = catalog.get(item).stack_limit
+			.map_or(n, |limit| limit.saturating_sub(self.count(item)));
+
+		let kept = n.min(space);
 
-	pub fn add_n(&mut self, item: Item, n: usize) {
 		match item {
-			Item::Treasure => { self.treasure += n as i32 }
-			Item::Food => { self.food += n as i32 }
+			Item::Treasure => { self.treasure += kept as i32 }
+			Item::Food => { self.food += kept as i32 }
 			_ => {
 				use std::iter;
-				self.items.extend(iter::repeat(item).take(n))
+				self.items.extend(iter::repeat(item).take(kept))
 			}
 		}
+
+		let surplus = n - kept;
+		self.surplus.extend(std::iter::repeat(item).take(surplus));
+		surplus
+	}
+
+	/// Moves anything carried beyond its stack limit into surplus
+	pub fn enforce_stack_limits(&mut self, catalog: &ItemCatalog) {
+		let items = std::mem::replace(&mut self.items, Vec::new());
+		for item in items {
+			self.add(catalog, item);
+		}
 	}
 
 	pub fn take(&mut self, item: Item) -> bool { self.take_n(item, 1) }
@@ -211,6 +657,18 @@ impl Inventory {
 		true
 	}
 
+	pub fn take_surplus_n(&mut self, item: Item, n: usize) -> bool {
+		if self.count_surplus(item) < n {
+			return false
+		}
+
+		for _ in 0..n {
+			self.surplus.remove_item(&item);
+		}
+
+		true
+	}
+
 	pub fn count(&self, item: Item) -> usize {
 		match item {
 			Item::Treasure => self.treasure as usize,
@@ -223,6 +681,16 @@ impl Inventory {
 
 	pub fn has(&self, item: Item) -> bool { self.count(item) > 0 }
 
+	pub fn count_surplus(&self, item: Item) -> usize {
+		self.surplus.iter()
+			.filter(|&&i| i == item)
+			.count()
+	}
+
+	pub fn iter_surplus(&self) -> impl Iterator<Item=Item> + '_ {
+		self.surplus.iter().cloned()
+	}
+
 	pub fn iter_items(&self) -> impl Iterator<Item=Item> + '_ {
 		self.items.iter().cloned()
 	}
@@ -234,4 +702,73 @@ impl Inventory {
 				_ => None,
 			})
 	}
-}
\ No newline at end of file
+}
+
+/// The data files the game ships with, for tests to play with
+#[cfg(test)]
+pub fn test_data() -> GameData {
+	use crate::data::DataFile;
+
+	GameData {
+		bestiary: Bestiary::load_from_file(DEFAULT_ENEMIES_PATH).unwrap(),
+		catalog: ItemCatalog::load_from_file(DEFAULT_ITEMS_PATH).unwrap(),
+		encounters: EncounterTables::load_from_file(crate::room::DEFAULT_ENCOUNTERS_PATH).unwrap(),
+		generator: GeneratorSettings::default(),
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Everything a seed decides on: the map and what's in it, which enemies turn up, and how battles roll
+	fn play_out(seed: u64, data: GameData) -> (String, String, Vec<i32>) {
+		let mut state = GameState::new(seed, data);
+		state.generate_map();
+
+		let monsters: Vec<Location> = state.map.iter()
+			.filter(|(_, room)| room.encounter == Some(crate::room::EncounterType::Monster))
+			.map(|(loc, _)| loc)
+			.collect();
+
+		for loc in monsters {
+			state.spawn_enemy_at(loc, false);
+		}
+
+		let rolls = (0..20).map(|_| state.rng.borrow_mut().gen_range(0, 10)).collect();
+
+		(format!("{:?}", state.map), format!("{:?}", state.enemies), rolls)
+	}
+
+	#[test]
+	fn same_seed_plays_out_the_same() {
+		let data = test_data();
+
+		for seed in 0..20 {
+			assert_eq!(play_out(seed, data), play_out(seed, data), "seed {} played out differently", seed);
+		}
+	}
+
+	#[test]
+	fn different_seeds_play_out_differently() {
+		let data = test_data();
+		assert_ne!(play_out(1, data), play_out(2, data));
+	}
+
+	#[test]
+	fn restarting_reseeds_in_place() {
+		let data = test_data();
+
+		let mut restarted = GameState::new(1, data);
+		restarted.generate_map();
+		restarted.rng.borrow_mut().gen::<u64>();
+		restarted.restart(5);
+
+		let mut fresh = GameState::new(5, data);
+		fresh.generate_map();
+
+		assert_eq!(format!("{:?}", restarted.map), format!("{:?}", fresh.map));
+		assert_eq!(restarted.rng.borrow_mut().gen::<u64>(), fresh.rng.borrow_mut().gen::<u64>());
+	}
+}
@@ -1,8 +1,13 @@
 use crate::prelude::*;
-use rand::distributions::{Standard, Distribution};
 
+use std::fmt;
+use std::cell::Cell;
+use crate::data::DataFile;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub const DEFAULT_ITEMS_PATH: &str = "data/items.ron";
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Item {
 	Food,
 	Treasure,
@@ -13,41 +18,360 @@ pub enum Item {
 	Equipment(Equipment),
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Equipment {
-	Sword,
-	Shield,
-	Axe,
+
+/// A piece of equipment, and all of its stats. Only the catalog hands these out, since they point into it
+#[derive(Copy, Clone)]
+pub struct Equipment(&'static ItemDefinition);
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EquipSlot {
+	Weapon,
 	Armour,
+	Offhand,
 }
 
 
 impl Equipment {
-	pub fn attack_bonus(self) -> i32 {
-		match self {
-			Equipment::Sword => 1,
-			Equipment::Axe => 2,
-			_ => 0
+	pub fn name(self) -> &'static str { &self.0.name }
+
+	pub fn slot(self) -> EquipSlot { self.0.slot().unwrap() }
+	pub fn attack(self) -> i32 { self.0.attack }
+	pub fn defense(self) -> i32 { self.0.defense }
+}
+
+/// Compared by name rather than by where they point, since names are unique within a catalog
+/// and equipment from two copies of the same catalog should still match
+impl PartialEq for Equipment {
+	fn eq(&self, other: &Equipment) -> bool { self.name() == other.name() }
+}
+
+impl Eq for Equipment {}
+
+impl PartialOrd for Equipment {
+	fn partial_cmp(&self, other: &Equipment) -> Option<std::cmp::Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for Equipment {
+	fn cmp(&self, other: &Equipment) -> std::cmp::Ordering { self.name().cmp(other.name()) }
+}
+
+/// Equipment reads just like the enum it used to be in messages
+impl fmt::Debug for Equipment {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+/// Written out by name, the same way as the enum it used to be
+impl Serialize for Equipment {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_unit_variant("Equipment", 0, self.name())
+	}
+}
+
+/// Only works inside `ItemCatalog::deserialize_with`, since turning a name back into equipment needs
+/// a catalog to look it up in
+impl<'de> Deserialize<'de> for Equipment {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		use serde::de::Error;
+
+		let VariantName(name) = VariantName::deserialize(deserializer)?;
+
+		let catalog = DESERIALIZING_WITH.with(Cell::get)
+			.ok_or_else(|| D::Error::custom(format!("There's no item catalog to look up {} in", name)))?;
+
+		catalog.find_equipment(&name)
+			.ok_or_else(|| D::Error::custom(format!("{} isn't in the item catalog", name)))
+	}
+}
+
+
+impl EquipSlot {
+	pub fn iter_all() -> impl Iterator<Item=EquipSlot> {
+		[EquipSlot::Weapon, EquipSlot::Armour, EquipSlot::Offhand].iter().cloned()
+	}
+}
+
+impl std::fmt::Display for EquipSlot {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match *self {
+			EquipSlot::Weapon  => write!(f, "weapon"),
+			EquipSlot::Armour  => write!(f, "armour"),
+			EquipSlot::Offhand => write!(f, "offhand"),
 		}
 	}
+}
+
+
+/// What an item does. Everything but equipment has exactly one entry in the catalog
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+pub enum ItemKind {
+	Food,
+	Treasure,
+	Map,
+	Key,
+	Potion,
+
+	Equipment(EquipSlot),
+}
+
+/// Everything there is to know about an item, as described in the item catalog file
+#[derive(Debug, Deserialize)]
+pub struct ItemDefinition {
+	/// What the player calls it when buying, selling or equipping it. Saves refer to equipment by name,
+	/// so this has to be a plain identifier
+	pub name: String,
+	pub kind: ItemKind,
+
+	/// Other names the player can use for it, like other spellings. Saves only ever use `name`
+	#[serde(default)]
+	pub aliases: Vec<String>,
+
+	/// Only equipment can have these
+	#[serde(default)]
+	pub attack: i32,
+
+	#[serde(default)]
+	pub defense: i32,
+
+	/// What the merchant charges for one, if they sell it at all
+	#[serde(default)]
+	pub buy_price: Option<usize>,
 
-	pub fn defense_bonus(self) -> i32 {
-		match self {
-			Equipment::Shield => 1,
-			Equipment::Armour => 2,
-			_ => 0
+	/// What the merchant pays for one, if they'll take it at all
+	#[serde(default)]
+	pub sell_price: Option<usize>,
+
+	/// How many the player can carry at once, if there's a limit at all
+	#[serde(default)]
+	pub stack_limit: Option<usize>,
+
+	/// How likely this is to turn up in a chest, or in an equipment room if it's equipment. Zero means never
+	#[serde(default)]
+	pub loot_weight: i32,
+}
+
+impl ItemDefinition {
+	pub fn item(&'static self) -> Item {
+		match self.kind {
+			ItemKind::Food => Item::Food,
+			ItemKind::Treasure => Item::Treasure,
+			ItemKind::Map => Item::Map,
+			ItemKind::Key => Item::Key,
+			ItemKind::Potion => Item::Potion,
+			ItemKind::Equipment(_) => Item::Equipment(Equipment(self)),
+		}
+	}
+
+	pub fn slot(&self) -> Option<EquipSlot> {
+		match self.kind {
+			ItemKind::Equipment(slot) => Some(slot),
+			_ => None,
 		}
 	}
+
+	/// Whether `name` is this item's name or one of its aliases, ignoring case
+	pub fn is_called(&self, name: &str) -> bool {
+		self.name.eq_ignore_ascii_case(name)
+			|| self.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(name))
+	}
+}
+
+
+/// Every item there is, as described in the item catalog file
+#[derive(Debug)]
+pub struct ItemCatalog {
+	definitions: Vec<ItemDefinition>,
+}
+
+thread_local! {
+	/// Where equipment is looked up while deserializing. See `ItemCatalog::deserialize_with`
+	static DESERIALIZING_WITH: Cell<Option<&'static ItemCatalog>> = Cell::new(None);
 }
 
+impl DataFile for ItemCatalog {
+	type Contents = Vec<ItemDefinition>;
+	const NAME: &'static str = "item catalog";
 
-impl Distribution<Equipment> for Standard {
-	fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Equipment {
-		[
-			Equipment::Sword,
-			Equipment::Shield,
-			Equipment::Axe,
-			Equipment::Armour,
-		].choose(rng).cloned().unwrap()
+	fn from_contents(definitions: Vec<ItemDefinition>) -> ItemCatalog {
+		ItemCatalog { definitions }
 	}
-}
\ No newline at end of file
+
+	fn validate(&self) -> Vec<String> {
+		let mut problems = Vec::new();
+
+		for (index, def) in self.definitions.iter().enumerate() {
+			let name = &def.name;
+
+			let is_identifier = name.chars().next().map_or(false, |c| c.is_ascii_alphabetic())
+				&& name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+			if !is_identifier {
+				problems.push(format!("'{}' isn't a valid name, names must be a letter followed by letters, digits or underscores", name));
+			}
+
+			if self.definitions[..index].iter().any(|other| other.name.eq_ignore_ascii_case(name)) {
+				problems.push(format!("{} is defined more than once", name));
+			}
+
+			for alias in def.aliases.iter() {
+				// Clashes between two aliases are only reported by the second of the two
+				let is_taken = self.definitions.iter().enumerate()
+					.any(|(other_index, other)| other_index != index && other.name.eq_ignore_ascii_case(alias)
+						|| other_index < index && other.aliases.iter().any(|other_alias| other_alias.eq_ignore_ascii_case(alias)));
+
+				if is_taken {
+					problems.push(format!("{} can't also be called {}, another item already goes by that name", name, alias));
+				}
+			}
+
+			let is_equipment = def.slot().is_some();
+			if !is_equipment && self.definitions[..index].iter().any(|other| other.kind == def.kind) {
+				problems.push(format!("{} is another {:?}, there can only be one of each kind of item besides equipment", name, def.kind));
+			}
+
+			if !is_equipment && (def.attack != 0 || def.defense != 0) {
+				problems.push(format!("{} can't have attack or defense, only equipment can", name));
+			}
+
+			if def.kind == ItemKind::Treasure && (def.buy_price.is_some() || def.sell_price.is_some()) {
+				problems.push(format!("{} is what the merchant trades in, it can't be bought or sold itself", name));
+			}
+
+			if def.stack_limit == Some(0) {
+				problems.push(format!("{} has a stack limit of 0, so it could never be picked up", name));
+			}
+
+			if def.loot_weight < 0 {
+				problems.push(format!("{} can't have a negative loot weight", name));
+			}
+		}
+
+		let basic_kinds = [ItemKind::Food, ItemKind::Treasure, ItemKind::Map, ItemKind::Key, ItemKind::Potion];
+
+		for kind in basic_kinds.iter() {
+			if !self.definitions.iter().any(|def| def.kind == *kind) {
+				problems.push(format!("There needs to be an item of kind {:?}", kind));
+			}
+		}
+
+		let equipment_weight: i32 = self.definitions.iter()
+			.filter(|def| def.slot().is_some())
+			.map(|def| def.loot_weight.max(0))
+			.sum();
+
+		if equipment_weight <= 0 {
+			problems.push("There needs to be at least one piece of equipment with a loot weight above zero".to_owned());
+		}
+
+		problems
+	}
+}
+
+impl ItemCatalog {
+	/// Runs `f` with equipment being looked up in this catalog as it's deserialized
+	pub fn deserialize_with<R>(&'static self, f: impl FnOnce() -> R) -> R {
+		let previous = DESERIALIZING_WITH.with(|catalog| catalog.replace(Some(self)));
+		let result = f();
+		DESERIALIZING_WITH.with(|catalog| catalog.set(previous));
+		result
+	}
+
+	fn find_definition(&self, item: Item) -> Option<&ItemDefinition> {
+		self.definitions.iter()
+			.find(|def| match (item, def.kind) {
+				(Item::Food, ItemKind::Food)
+				| (Item::Treasure, ItemKind::Treasure)
+				| (Item::Map, ItemKind::Map)
+				| (Item::Key, ItemKind::Key)
+				| (Item::Potion, ItemKind::Potion) => true,
+
+				(Item::Equipment(e), ItemKind::Equipment(_)) => def.name == e.name(),
+				_ => false,
+			})
+	}
+
+	/// Anything that can't be found is a bug, since equipment only comes from the catalog,
+	/// and the catalog always has one of every other kind of item
+	pub fn get(&self, item: Item) -> &ItemDefinition {
+		self.find_definition(item)
+			.unwrap_or_else(|| panic!("{:?} isn't in the item catalog", item))
+	}
+
+	/// Looks up an item by name or alias, ignoring case
+	pub fn find(&'static self, name: &str) -> Option<Item> {
+		self.definitions.iter()
+			.find(|def| def.is_called(name))
+			.map(ItemDefinition::item)
+	}
+
+	pub fn find_equipment(&'static self, name: &str) -> Option<Equipment> {
+		match self.find(name) {
+			Some(Item::Equipment(e)) => Some(e),
+			_ => None,
+		}
+	}
+
+	pub fn iter_equipment(&'static self) -> impl Iterator<Item=Equipment> {
+		self.definitions.iter()
+			.filter_map(|def| match def.item() {
+				Item::Equipment(e) => Some(e),
+				_ => None,
+			})
+	}
+
+	pub fn choose_equipment(&'static self, rng: &mut impl Rng) -> Equipment {
+		let options: Vec<Equipment> = self.iter_equipment().collect();
+		let weights: Vec<i32> = options.iter().map(|e| e.0.loot_weight).collect();
+
+		choose_with_weights(&options, &weights, rng)
+	}
+
+	/// Up to `count` different items, picked by loot weight
+	pub fn choose_loot(&'static self, count: usize, rng: &mut impl Rng) -> Vec<Item> {
+		let mut options: Vec<&ItemDefinition> = self.definitions.iter()
+			.filter(|def| def.loot_weight > 0)
+			.collect();
+
+		let mut loot = Vec::new();
+
+		while loot.len() < count && !options.is_empty() {
+			let weights: Vec<i32> = options.iter().map(|def| def.loot_weight).collect();
+			let index = choose_with_weights(&(0..options.len()).collect::<Vec<_>>(), &weights, rng);
+
+			loot.push(options.remove(index).item());
+		}
+
+		loot
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn catalog(contents: &str) -> ItemCatalog {
+		ItemCatalog::from_contents(ron::de::from_str(contents).unwrap())
+	}
+
+	#[test]
+	fn items_can_be_found_by_alias() {
+		let catalog = ItemCatalog::load_from_file(DEFAULT_ITEMS_PATH).unwrap();
+
+		assert_eq!(catalog.find_equipment("armor"), catalog.find_equipment("Armour"));
+		assert_eq!(catalog.find_equipment("armor").map(Equipment::name), Some("Armour"));
+	}
+
+	#[test]
+	fn aliases_cant_be_taken_twice() {
+		let problems = catalog(r#"[
+			(name: "Sword", kind: Equipment(Weapon), aliases: ["Blade"]),
+			(name: "Dagger", kind: Equipment(Weapon), aliases: ["blade", "Sword"]),
+		]"#).validate();
+
+		assert_eq!(problems.iter().filter(|p| p.contains("can't also be called")).count(), 2, "{:?}", problems);
+		assert!(problems.iter().any(|p| p.contains("called blade")), "{:?}", problems);
+		assert!(problems.iter().any(|p| p.contains("called Sword")), "{:?}", problems);
+	}
+}
@@ -9,62 +9,578 @@ mod task;
 mod types;
 mod gamestate;
 mod map;
+mod mapgen;
 mod item;
 mod room;
 mod view;
 mod controller;
 mod enemy;
+mod trap;
+mod save;
+mod replay;
+mod session;
+mod event;
+mod score;
+mod data;
 
 use prelude::*;
-use gamestate::GameState;
+use gamestate::{GameState, GameData};
+use item::ItemCatalog;
+use mapgen::GeneratorSettings;
+use data::DataFile;
 use view::View;
-use task::Executor;
+use replay::{Recorder, ReplayView};
+use session::Session;
 
 fn main() {
-	if std::env::args().find(|s| s == "--text").is_some() {
-		run_with_view(view::TextView::new());
+	if has_arg("--scores") {
+		print_high_scores();
+		return;
+	}
+
+	let data = load_game_data();
+
+	if let Some(count) = arg_value::<u64>("--check-maps") {
+		check_map_generators(count, data);
+		return;
+	}
+
+	if let Some(path) = arg_value::<String>("--replay") {
+		run_replay(&path, data);
+		return;
+	}
+
+	if let Some(seeds) = arg_value::<String>("--seeds") {
+		let path = arg_value::<String>("--script")
+			.unwrap_or_else(|| {
+				eprintln!("--seeds needs a --script to play through");
+				std::process::exit(1);
+			});
+
+		run_script_seeds(&path, &seeds, data);
+		return;
+	}
+
+	let script = arg_value::<String>("--script");
+
+	// Scripts don't wait on anyone, so there's no putting them under time pressure
+	let realtime = has_arg("--realtime") && script.is_none();
+
+	let (gamestate, recorder) = match arg_value::<String>("--load") {
+		// Replays start from a seed, so there's no replaying a loaded game
+		Some(path) => (load_gamestate(&path, data), None),
+		None => {
+			let gamestate = generate_gamestate(arg_value("--seed").unwrap_or_else(random), data);
+
+			// Nor is there replaying a realtime game, since timeouts aren't recorded
+			let recorder = if realtime { None } else { start_recording(&gamestate) };
+			(gamestate, recorder)
+		}
+	};
+
+	println!("Seed: {}", gamestate.seed);
+
+	if let Some(path) = script {
+		run_script(&path, gamestate, recorder);
+	} else if has_arg("--text") {
+		play(view::TextView::new(data.catalog), gamestate, recorder, realtime);
 	} else {
-		run_with_view(view::GfxView::new());
+		play(view::GfxView::new(data.catalog), gamestate, recorder, realtime);
 	}
 }
 
 
-fn run_with_view(mut view: impl View) {
-	let gamestate = generate_gamestate();
-	let gamestate = Rc::new(RefCell::new(gamestate));
+fn has_arg(name: &str) -> bool {
+	std::env::args().any(|s| s == name)
+}
+
+fn arg_value<T: std::str::FromStr>(name: &str) -> Option<T> {
+	let value = std::env::args()
+		.skip_while(|s| s != name)
+		.nth(1)?;
+
+	let parsed = value.parse()
+		.unwrap_or_else(|_| panic!("Invalid value '{}' for {}", value, name));
+
+	Some(parsed)
+}
+
+fn scores_path() -> String {
+	arg_value::<String>("--scores-file")
+		.unwrap_or_else(|| score::DEFAULT_SCORES_PATH.to_owned())
+}
+
+
+/// Plays a game for real, keeping score in `--scores-file <path>`, or in the default scores path
+fn play<V: View>(view: V, gamestate: GameState, recorder: Option<Recorder>, realtime: bool) {
+	let session = Session::new(view, gamestate, recorder, realtime);
+	session.keep_score(score::ScoreKeeper::new(&scores_path()));
+	run_session(session);
+}
 
-	unsafe {
-		let executor = task::Executor::new(Rc::clone(&gamestate));
-		EXECUTOR = Some(executor);
+fn run_session<V: View>(mut session: Session<V>) -> (V, GameState) {
+	if let Some(path) = arg_value::<String>("--event-log") {
+		match event::EventLog::create(&path) {
+			Ok(log) => session.subscribe(log),
+			Err(err) => eprintln!("Failed to start event log '{}', continuing without: {}", path, err),
+		}
 	}
 
-	let ctl_task = get_executor().queue(controller::run_main_controller());
+	while session.step() {}
+	session.finish()
+}
 
-	view.init(&gamestate.borrow());
+/// Records to `--record <path>`, if it was passed
+fn start_recording(gamestate: &GameState) -> Option<Recorder> {
+	let path = arg_value::<String>("--record")?;
 
-	while get_executor().is_task_running(ctl_task) && !view.should_quit() {
-		get_executor().resume_tasks();
-		get_executor().process_commands(&mut gamestate.borrow_mut(), &mut view);
-		view.update(&gamestate.borrow());
+	match Recorder::create(&path, gamestate.seed, gamestate.data.generator) {
+		Ok(recorder) => Some(recorder),
+		Err(err) => {
+			eprintln!("Failed to start recording to '{}', continuing without: {}", path, err);
+			None
+		}
 	}
 }
 
+/// Plays back a replay through whichever view was asked for, then checks that the game ended up
+/// where it did when it was recorded
+fn run_replay(path: &str, data: GameData) {
+	let replay = replay::Replay::load_from_file(path, data.catalog)
+		.unwrap_or_else(|err| {
+			eprintln!("Failed to load replay '{}': {}", path, err);
+			std::process::exit(1);
+		});
 
-static mut EXECUTOR: Option<Executor> = None;
+	let gamestate = generate_gamestate(replay.seed, GameData { generator: replay.generator, .. data });
+	println!("Seed: {}", gamestate.seed);
 
-pub fn get_executor() -> &'static Executor {
-	unsafe {
-		EXECUTOR.as_ref()
-			.expect("Executor not initialised!")
+	let gamestate = if has_arg("--text") {
+		run_session(Session::new(ReplayView::new(view::TextView::new(data.catalog), &replay), gamestate, None, false)).1
+	} else {
+		run_session(Session::new(ReplayView::new(view::GfxView::new(data.catalog), &replay), gamestate, None, false)).1
+	};
+
+	match replay.check_final_state(&gamestate) {
+		Ok(()) => println!("Replay finished"),
+		Err(err) => {
+			eprintln!("Replay '{}' couldn't be verified: {}", path, err);
+			std::process::exit(1);
+		}
 	}
 }
 
+/// Plays through a script of commands without any input or output, then writes out a transcript of
+/// everything that happened if `--transcript <path>` was passed
+fn run_script(path: &str, gamestate: GameState, recorder: Option<Recorder>) {
+	let script = load_script(path, gamestate.data.catalog);
+	let (view, _) = run_session(Session::new(script, gamestate, recorder, false));
 
-fn generate_gamestate() -> GameState {
-	let mut state = GameState::new();
+	if let Some(transcript_path) = arg_value::<String>("--transcript") {
+		write_transcript(&transcript_path, view.transcript());
+	}
+}
+
+/// Plays the same script through a game for each of a comma separated list of seeds, all side by side.
+/// Nothing gets recorded, but the transcripts of every game are written one after the other
+/// if `--transcript <path>` was passed
+fn run_script_seeds(path: &str, seeds: &str, data: GameData) {
+	let seeds: Vec<u64> = seeds.split(',')
+		.map(|seed| seed.trim().parse().unwrap_or_else(|_| panic!("Invalid seed '{}' for --seeds", seed)))
+		.collect();
+
+	let mut sessions: Vec<Session<view::ScriptedView>> = seeds.iter()
+		.map(|&seed| Session::new(load_script(path, data.catalog), generate_gamestate(seed, data), None, false))
+		.collect();
 
-	let mut map_builder = map::MapBuilder::new(&mut state.map);
-	map_builder.generate_random_walk();
+	let mut any_running = true;
+	while any_running {
+		any_running = false;
+
+		for session in sessions.iter_mut() {
+			any_running |= session.step();
+		}
+	}
+
+	let mut transcript = Vec::new();
+
+	for (seed, session) in seeds.iter().zip(sessions) {
+		let (view, gamestate) = session.finish();
+		println!("Seed {}: finished at {:?} with {} health", seed, gamestate.player.location, gamestate.player.health);
+
+		transcript.push(format!("=== Seed: {} ===", seed));
+		transcript.extend_from_slice(view.transcript());
+	}
+
+	if let Some(transcript_path) = arg_value::<String>("--transcript") {
+		write_transcript(&transcript_path, &transcript);
+	}
+}
+
+/// Prints the best runs on every seed that's been played to the end
+fn print_high_scores() {
+	let path = scores_path();
+	let scores = score::HighScores::load_from_file(&path)
+		.unwrap_or_else(|err| {
+			eprintln!("Failed to load high scores '{}': {}", path, err);
+			std::process::exit(1);
+		});
+
+	let mut any_scores = false;
+
+	for (seed, entries) in scores.iter() {
+		any_scores = true;
+		println!("Seed {}:", seed);
+
+		for (rank, entry) in entries.iter().enumerate() {
+			let summary = &entry.summary;
+			println!("  {:>2}. {:>6}  {:?}, {} rooms, {} kills ({} bosses), {} treasure, {} turns",
+				rank + 1, entry.score, summary.outcome, summary.rooms_explored,
+				summary.enemies_killed, summary.bosses_killed, summary.treasure, summary.turns);
+		}
+	}
+
+	if !any_scores {
+		println!("No high scores yet");
+	}
+}
+
+fn load_script(path: &str, catalog: &'static ItemCatalog) -> view::ScriptedView {
+	view::ScriptedView::from_file(path, catalog)
+		.unwrap_or_else(|err| {
+			eprintln!("Failed to load script '{}': {}", path, err);
+			std::process::exit(1);
+		})
+}
+
+fn write_transcript(path: &str, lines: &[String]) {
+	let mut transcript = lines.join("\n");
+	transcript.push('\n');
+
+	if let Err(err) = std::fs::write(path, transcript) {
+		eprintln!("Failed to write transcript '{}': {}", path, err);
+		std::process::exit(1);
+	}
+}
+
+
+/// Loads every data file, bailing out on the first one that's missing or invalid
+fn load_game_data() -> GameData {
+	GameData {
+		bestiary: load_data_file("--enemies", enemy::DEFAULT_ENEMIES_PATH),
+		catalog: load_data_file("--items", item::DEFAULT_ITEMS_PATH),
+		encounters: load_data_file("--encounters", room::DEFAULT_ENCOUNTERS_PATH),
+		generator: map_generator(),
+	}
+}
+
+/// Loads a data file from `<flag> <path>`, or from `default_path`
+fn load_data_file<T: DataFile>(flag: &str, default_path: &str) -> &'static T {
+	let path = arg_value::<String>(flag)
+		.unwrap_or_else(|| default_path.to_owned());
+
+	T::load_from_file(&path)
+		.unwrap_or_else(|err| {
+			eprintln!("Failed to load {} '{}': {}", T::NAME, path, err);
+			std::process::exit(1);
+		})
+}
+
+/// Picks a map generator with `--generator <name>`, or the random walk by default. Its settings can be changed with
+/// `--steps`, `--rooms`, `--loops` and `--branchiness`, whichever of those it has
+fn map_generator() -> GeneratorSettings {
+	match arg_value::<String>("--generator") {
+		Some(name) => map_generator_named(&name),
+		None => map_generator_named("walk"),
+	}
+}
+
+fn map_generator_named(name: &str) -> GeneratorSettings {
+	use mapgen::*;
+
+	let generator = match GeneratorSettings::named(name) {
+		Some(GeneratorSettings::RandomWalk(default)) => GeneratorSettings::RandomWalk(RandomWalk {
+			steps: arg_value("--steps").unwrap_or(default.steps),
+		}),
+
+		Some(GeneratorSettings::Bsp(default)) => GeneratorSettings::Bsp(BspGrid {
+			rooms: arg_value("--rooms").unwrap_or(default.rooms),
+			loop_factor: arg_value("--loops").unwrap_or(default.loop_factor),
+		}),
+
+		Some(GeneratorSettings::Cave(default)) => GeneratorSettings::Cave(Cave {
+			rooms: arg_value("--rooms").unwrap_or(default.rooms),
+			loop_factor: arg_value("--loops").unwrap_or(default.loop_factor),
+		}),
+
+		Some(GeneratorSettings::Maze(default)) => GeneratorSettings::Maze(MazeWithLoops {
+			rooms: arg_value("--rooms").unwrap_or(default.rooms),
+			loop_factor: arg_value("--loops").unwrap_or(default.loop_factor),
+			branchiness: arg_value("--branchiness").unwrap_or(default.branchiness),
+		}),
+
+		None => {
+			eprintln!("Unknown map generator '{}', expected one of: {}", name, GeneratorSettings::NAMES.join(", "));
+			std::process::exit(1);
+		}
+	};
+
+	if let Err(err) = generator.validate() {
+		eprintln!("Invalid settings for map generator '{}': {}", name, err);
+		std::process::exit(1);
+	}
+
+	generator
+}
 
+/// Generates `count` dungeons with each map generator, or just the one picked with `--generator`, and reports
+/// how many needed repairing, along with anything that's still wrong with them afterwards.
+/// Exits with an error if anything is
+fn check_map_generators(count: u64, data: GameData) {
+	let generators: Vec<GeneratorSettings> = if has_arg("--generator") {
+		vec![data.generator]
+	} else {
+		GeneratorSettings::NAMES.iter().map(|name| map_generator_named(name)).collect()
+	};
+
+	let mut any_problems = false;
+
+	for generator in generators {
+		let mut repaired_maps = 0;
+		let mut broken_maps = 0;
+		let mut total_rooms = 0;
+
+		for seed in 0..count {
+			let mut gamestate = GameState::new(seed, GameData { generator, .. data });
+			let repaired = gamestate.generate_map();
+			let problems = gamestate.map.validate();
+
+			total_rooms += gamestate.map.iter().count();
+
+			if !repaired.is_empty() {
+				repaired_maps += 1;
+
+				if has_arg("--verbose") {
+					for problem in repaired {
+						println!("{} seed {}: repaired: {}", generator.name(), seed, problem);
+					}
+				}
+			}
+
+			if !problems.is_empty() {
+				broken_maps += 1;
+
+				for problem in problems {
+					println!("{} seed {}: {}", generator.name(), seed, problem);
+				}
+			}
+		}
+
+		println!("{}: {} of {} maps needed repairing and {} are still broken, {:.1} rooms on average",
+			generator.name(), repaired_maps, count, broken_maps, total_rooms as f64 / count.max(1) as f64);
+
+		any_problems |= broken_maps > 0;
+	}
+
+	if any_problems {
+		std::process::exit(1);
+	}
+}
+
+fn generate_gamestate(seed: u64, data: GameData) -> GameState {
+	let mut state = GameState::new(seed, data);
+	state.generate_map();
 	state
-}
\ No newline at end of file
+}
+
+fn load_gamestate(path: &str, data: GameData) -> GameState {
+	let mut state = GameState::new(0, data);
+
+	if let Err(err) = save::load_from_file(&mut state, path) {
+		eprintln!("Failed to load '{}': {}", path, err);
+		std::process::exit(1);
+	}
+
+	state
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use gamestate::test_data;
+	use item::Item;
+
+	/// Plays `script` through a whole game, and hands back the transcript along with the state the game finished in
+	fn play_script(seed: u64, script: &[&str]) -> (Vec<String>, GameState) {
+		let data = test_data();
+		let lines: Vec<String> = script.iter().map(|&line| line.to_owned()).collect();
+
+		let mut session = Session::new(view::ScriptedView::from_lines(lines, data.catalog), generate_gamestate(seed, data), None, false);
+		while session.step() {}
+
+		let (view, gamestate) = session.finish();
+		(view.transcript().to_vec(), gamestate)
+	}
+
+	fn count_lines(transcript: &[String], prefix: &str) -> usize {
+		transcript.iter().filter(|line| line.starts_with(prefix)).count()
+	}
+
+	/// Adds up the number straight after `prefix` on every line starting with it
+	fn sum_amounts(transcript: &[String], prefix: &str) -> i32 {
+		transcript.iter()
+			.filter(|line| line.starts_with(prefix))
+			.map(|line| line[prefix.len()..].split(',').next().unwrap().parse::<i32>().unwrap())
+			.sum()
+	}
+
+	const WANDER: &[&str] = &["n", "e", "s", "w", "n", "n", "e", "e", "s", "s", "w", "w", "i", "m"];
+
+	#[test]
+	fn same_seed_and_script_play_out_the_same() {
+		// Debug commands only work in the main mode, so they have to come before anything the wandering might run into
+		let mut script = vec!["d merchant", "b potion", "l", "d battle"];
+		script.extend(std::iter::repeat("f").take(30));
+		script.extend_from_slice(WANDER);
+
+		for seed in 0..10 {
+			let (first_transcript, first_state) = play_script(seed, &script);
+			let (second_transcript, second_state) = play_script(seed, &script);
+
+			assert_eq!(first_transcript, second_transcript, "seed {} played out differently", seed);
+			assert_eq!(save::snapshot(&first_state).unwrap(), save::snapshot(&second_state).unwrap(),
+				"seed {} finished differently", seed);
+		}
+	}
+
+	#[test]
+	fn moving_keeps_track_of_turns_and_location() {
+		let mut total_turns = 0;
+
+		for seed in 0..10 {
+			let (transcript, state) = play_script(seed, WANDER);
+			total_turns += state.turns;
+
+			assert_eq!(state.turns, count_lines(&transcript, "RoomEntered"), "seed {}", seed);
+
+			let last_location = transcript.iter().rev()
+				.filter(|line| line.starts_with("RoomEntered") || line.starts_with("PlayerTeleported"))
+				.filter_map(|line| line.split("location: ").nth(1))
+				.map(|rest| &rest[..=rest.find(')').unwrap()])
+				.next()
+				.unwrap_or("Location(0, 0)");
+
+			assert_eq!(format!("{:?}", state.player.location), last_location, "seed {}", seed);
+			assert!(state.map.visited(state.player.location) || state.player.is_dead(), "seed {}", seed);
+		}
+
+		assert!(total_turns > 0, "Nobody managed to move anywhere");
+	}
+
+	#[test]
+	fn merchant_trades_items_for_treasure() {
+		let (transcript, state) = play_script(1, &["d merchant", "b potion", "s potion", "b map", "b map", "l", "q"]);
+		let inventory = &state.player.inventory;
+
+		// Starting with 5 treasure, a potion costs 5 and sells for 5, and a map costs 3
+		assert_eq!(inventory.count(Item::Treasure), 2);
+		assert_eq!(inventory.count(Item::Potion), 0);
+		assert_eq!(inventory.count(Item::Map), 1);
+
+		assert_eq!(count_lines(&transcript, "ItemGained { item: Potion"), 1);
+		assert_eq!(count_lines(&transcript, "ItemConsumed { item: Potion"), 1);
+		assert_eq!(count_lines(&transcript, "ItemGained { item: Map"), 1);
+		assert_eq!(count_lines(&transcript, "ItemConsumed { item: Treasure, count: 3"), 1);
+		assert!(transcript.contains(&"[mode transition -> [Main, Merchant]]".to_owned()));
+	}
+
+	#[test]
+	fn merchant_picks_out_equipment_from_the_seed() {
+		let script = ["d g treasure 5", "d merchant", "b equipment", "l", "q"];
+
+		for seed in 0..5 {
+			let (transcript, state) = play_script(seed, &script);
+
+			assert_eq!(play_script(seed, &script).0, transcript, "seed {} picked differently", seed);
+			assert_eq!(count_lines(&transcript, "ItemGained { item: Equipment("), 1, "seed {}", seed);
+
+			// Starting with 5 treasure, and every piece of equipment costs 5
+			assert_eq!(state.player.inventory.count(Item::Treasure), 5, "seed {}", seed);
+		}
+	}
+
+	#[test]
+	fn only_traps_that_didnt_go_off_can_be_disarmed() {
+		let mut sprung = 0;
+		let mut disarmable = 0;
+
+		for seed in 0..20 {
+			let (transcript, _) = play_script(seed, &["d trap", "leave", "q"]);
+
+			let went_off = transcript.iter().any(|line| line.starts_with("ItemDropped") || line.contains("reason: Trap"));
+			let offered_disarm = transcript.contains(&"[mode transition -> [Main, Disarm]]".to_owned());
+
+			assert!(!(went_off && offered_disarm), "seed {} offered to disarm a trap that already went off", seed);
+
+			sprung += went_off as usize;
+			disarmable += offered_disarm as usize;
+		}
+
+		assert!(sprung > 0 && disarmable > 0, "{} sprung and {} disarmable, expected some of each", sprung, disarmable);
+	}
+
+	#[test]
+	fn fighting_with_plenty_of_health_kills_the_enemy() {
+		let mut script = vec!["d g axe", "equip axe", "d g health 100", "d battle"];
+		script.extend(std::iter::repeat("f").take(100));
+		script.push("q");
+
+		for seed in 0..5 {
+			let (transcript, state) = play_script(seed, &script);
+			let location = state.player.location;
+
+			assert_eq!(count_lines(&transcript, "ItemEquipped"), 1, "seed {}", seed);
+			assert_eq!(state.enemies_killed, 1, "seed {}", seed);
+			assert_eq!(count_lines(&transcript, "EnemyKilled"), 1, "seed {}", seed);
+			assert!(state.get_enemy(location).map_or(true, |enemy| enemy.is_dead()), "seed {}", seed);
+
+			// Everything the player lost is accounted for in the transcript
+			let lost = sum_amounts(&transcript, "PlayerDamaged { amount: ");
+			let healed = sum_amounts(&transcript, "PlayerHealed { amount: ");
+			assert_eq!(state.player.health, 15 + healed - lost, "seed {}", seed);
+		}
+	}
+
+	#[test]
+	fn dying_shows_the_score() {
+		// Three fights without a weapon is more than the player can take on this seed
+		let mut script = Vec::new();
+		for _ in 0..3 {
+			script.push("d battle");
+			script.extend(std::iter::repeat("f").take(20));
+		}
+		script.push("q");
+
+		let (transcript, gamestate) = play_script(1, &script);
+		assert!(gamestate.player.is_dead());
+
+		let summary = gamestate.summary();
+		let expected = format!("[game over: {:?}, score {}, rank None]", summary, score::calculate(&summary));
+
+		let fatal_blow = transcript.iter().position(|line| line.contains("fatal: true"))
+			.unwrap_or_else(|| panic!("no fatal blow in {:#?}", transcript));
+		let game_over = transcript.iter().position(|line| *line == expected)
+			.unwrap_or_else(|| panic!("no '{}' in {:#?}", expected, transcript));
+
+		// The game ends as soon as the fight does, rather than waiting for another command to come in
+		assert!(fatal_blow < game_over);
+		assert!(!transcript[fatal_blow..game_over].iter().any(|line| line.starts_with("> ")),
+			"{:#?}", &transcript[fatal_blow..=game_over]);
+	}
+
+	#[test]
+	fn script_running_out_ends_the_game() {
+		let (transcript, _) = play_script(1, &["i"]);
+		assert_eq!(transcript.last().map(String::as_str), Some("[script finished]"));
+	}
+}
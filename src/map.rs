@@ -1,11 +1,13 @@
 use crate::prelude::*;
-use crate::room::Room;
-use std::collections::{HashMap, HashSet};
+use crate::room::{Room, EncounterType, EncounterTables};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Map {
-	rooms: HashMap<Location, Room>,
-	visited: HashSet<Location>,
+	// Ordered so that iteration, and therefore anything chosen from it, is reproducible from a seed
+	rooms: BTreeMap<Location, Room>,
+	visited: BTreeSet<Location>,
 }
 
 impl Map {
@@ -31,6 +33,7 @@ impl Map {
 
 	pub fn visited(&self, loc: Location) -> bool { self.visited.contains(&loc) }
 	pub fn mark_visited(&mut self, loc: Location) -> bool { self.visited.insert(loc) }
+	pub fn visited_count(&self) -> usize { self.visited.len() }
 
 	pub fn bounds(&self) -> Bounds {
 		self.rooms.keys()
@@ -42,6 +45,136 @@ impl Map {
 			.map(|(loc, room)| (*loc, room.clone()))
 	}
 
+	/// Every room that can be walked to from `start` through doors, without going through any rooms that haven't been generated yet
+	pub fn reachable_from(&self, start: Location) -> BTreeSet<Location> {
+		let mut reached = BTreeSet::new();
+		let mut queue = std::collections::VecDeque::new();
+
+		if self.has(start) {
+			reached.insert(start);
+			queue.push_back(start);
+		}
+
+		while let Some(location) = queue.pop_front() {
+			let room = self.rooms[&location];
+
+			for dir in room.iter_neighbor_directions() {
+				let neighbor = location.offset_in_direction(dir);
+				if self.has(neighbor) && reached.insert(neighbor) {
+					queue.push_back(neighbor);
+				}
+			}
+		}
+
+		reached
+	}
+
+	/// Whether the player knows a room is there and where its doors lead, either from having been there or
+	/// from having a map. What's in it is only known once it's been visited, besides the exit
+	pub fn is_known(&self, loc: Location, has_map: bool) -> bool {
+		self.has(loc) && (has_map || self.visited(loc))
+	}
+
+	/// Whether the player has seen something in a room that would rather they didn't walk through it
+	pub fn is_known_danger(&self, loc: Location) -> bool {
+		self.visited(loc) && matches!(
+			self.get(loc).and_then(|room| room.encounter),
+			Some(EncounterType::Monster) | Some(EncounterType::Trap) | Some(EncounterType::Boss)
+		)
+	}
+
+	/// The shortest way from `from` to `to` through rooms the player knows about, including both ends.
+	/// Known dangers are avoided along the way, although `to` itself can be dangerous
+	pub fn find_route(&self, from: Location, to: Location, has_map: bool) -> Option<Vec<Location>> {
+		let mut came_from = BTreeMap::new();
+		let mut queue = std::collections::VecDeque::new();
+
+		came_from.insert(from, from);
+		queue.push_back(from);
+
+		while let Some(location) = queue.pop_front() {
+			if location == to {
+				let mut route = vec![to];
+				let mut current = to;
+
+				while current != from {
+					current = came_from[&current];
+					route.push(current);
+				}
+
+				route.reverse();
+				return Some(route);
+			}
+
+			let room = self.rooms[&location];
+
+			for dir in room.iter_neighbor_directions() {
+				let neighbor = location.offset_in_direction(dir);
+				let passable = self.is_known(neighbor, has_map)
+					&& (neighbor == to || !self.is_known_danger(neighbor));
+
+				if passable && !came_from.contains_key(&neighbor) {
+					came_from.insert(neighbor, location);
+					queue.push_back(neighbor);
+				}
+			}
+		}
+
+		None
+	}
+
+	/// Everything that would get in the way of playing through the map. Doors leading to rooms that haven't
+	/// been generated yet are fine, since they'll be generated with a door back when they're entered
+	pub fn validate(&self) -> Vec<MapProblem> {
+		let start = Location(0, 0);
+		let mut problems = Vec::new();
+
+		if !self.has(start) {
+			problems.push(MapProblem::NoStartRoom);
+		}
+
+		let reachable = self.reachable_from(start);
+		let mut found_exit = false;
+
+		for (&location, room) in self.rooms.iter() {
+			if room.is_exit {
+				if location == start {
+					problems.push(MapProblem::ExitInStartRoom);
+				}
+
+				if found_exit {
+					problems.push(MapProblem::ExtraExit(location));
+				}
+
+				found_exit = true;
+			}
+
+			if !reachable.contains(&location) {
+				if room.is_exit {
+					problems.push(MapProblem::UnreachableExit(location));
+				} else {
+					problems.push(MapProblem::Unreachable(location));
+				}
+			}
+
+			if room.iter_neighbor_directions().next().is_none() {
+				problems.push(MapProblem::NoDoors(location));
+			}
+
+			for (dir, neighbor) in self.iter_neighbors(location) {
+				if room.door(dir) && !neighbor.door(dir.opposite()) {
+					problems.push(MapProblem::OneWayDoor(location, dir));
+				}
+			}
+		}
+
+		if !found_exit {
+			problems.push(MapProblem::NoExit);
+		}
+
+		problems
+	}
+
 	pub fn iter_neighbors(&self, location: Location) -> impl Iterator<Item=(Direction, Room)> + '_ {
 		Direction::iter_all()
 			.filter_map(move |dir| {
@@ -53,14 +186,47 @@ impl Map {
 }
 
 
+/// Something that would get in the way of a map being played through, as found by `Map::validate`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MapProblem {
+	NoStartRoom,
+	NoExit,
+	ExitInStartRoom,
+	ExtraExit(Location),
+	UnreachableExit(Location),
+	Unreachable(Location),
+	NoDoors(Location),
+
+	/// A door into a neighboring room that doesn't have a door back
+	OneWayDoor(Location, Direction),
+}
+
+impl fmt::Display for MapProblem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match *self {
+			MapProblem::NoStartRoom => write!(f, "There's no start room"),
+			MapProblem::NoExit => write!(f, "There's no exit"),
+			MapProblem::ExitInStartRoom => write!(f, "The exit is in the start room"),
+			MapProblem::ExtraExit(loc) => write!(f, "{:?} is an exit, but there's already one", loc),
+			MapProblem::UnreachableExit(loc) => write!(f, "The exit at {:?} can't be reached from the start room", loc),
+			MapProblem::Unreachable(loc) => write!(f, "{:?} can't be reached from the start room", loc),
+			MapProblem::NoDoors(loc) => write!(f, "{:?} has no doors", loc),
+			MapProblem::OneWayDoor(loc, dir) => write!(f, "The door {:?} out of {:?} has no door back", dir, loc),
+		}
+	}
+}
+
+
 #[derive(Debug)]
 pub struct MapBuilder<'m> {
-	map: &'m mut Map
+	map: &'m mut Map,
+	rng: &'m mut GameRng,
+	encounters: &'static EncounterTables,
 }
 
 impl<'m> MapBuilder<'m> {
-	pub fn new(map: &'m mut Map) -> MapBuilder<'m> {
-		MapBuilder { map }
+	pub fn new(map: &'m mut Map, rng: &'m mut GameRng, encounters: &'static EncounterTables) -> MapBuilder<'m> {
+		MapBuilder { map, rng, encounters }
 	}
 
 
@@ -92,51 +258,143 @@ impl<'m> MapBuilder<'m> {
 
 	pub fn generate_room_at(&mut self, location: Location) {
 		if !self.map.has(location) {
-			self.map.add(location, MapBuilder::generate_room());
+			let room = self.generate_room(location);
+			self.map.add(location, room);
 			self.ensure_room_connected(location);
 		}
 	}
 
-	fn generate_room() -> Room {
+	fn generate_room(&mut self, location: Location) -> Room {
 		Room {
-			doors: random(),
-			encounter: if rng().gen_bool(0.8) {
-				Some(random())
-			} else {
-				None
-			},
+			doors: self.rng.gen(),
+			encounter: self.encounters.choose(location, self.rng),
 			is_exit: false,
 		}
 	}
 
-	pub fn generate_random_walk(&mut self) {
-		let mut builder_loc = Location(0, 0);
+	/// Adds a room with a random set of doors, one of which is always `door`
+	pub fn add_random_room(&mut self, location: Location, door: Direction) {
+		if !self.map.has(location) {
+			let mut room = self.generate_room(location);
+			room.set_door(door, true);
+			self.map.add(location, room);
+
+			self.ensure_room_connected(location);
+		}
+	}
 
-		for _ in 0..50 {
-			// Walk through a door if possible, otherwise just pick a direction and pretend there's a door there
-			let walk_dir = if let Some(room) = self.map.get(builder_loc) {
-				room.iter_neighbor_directions().choose(&mut rng())
-					.unwrap_or_else(|| random())
-			} else {
-				random()
+	/// Adds a room with no doors of its own, for generators that want to connect things up themselves.
+	/// Any neighbors with doors leading here get doors back, same as when a room is generated on entry
+	pub fn add_room(&mut self, location: Location) {
+		if !self.map.has(location) {
+			let room = Room {
+				doors: [false; 4],
+				encounter: self.encounters.choose(location, self.rng),
+				is_exit: false,
 			};
 
-			builder_loc = builder_loc.offset_in_direction(walk_dir);
+			self.map.add(location, room);
+			self.ensure_room_connected(location);
+		}
+	}
+
+	/// Opens up a door in both rooms between `location` and its neighbor in `dir`, both of which have to exist
+	pub fn connect(&mut self, location: Location, dir: Direction) {
+		let neighbor_loc = location.offset_in_direction(dir);
+
+		let mut room = self.map.get(location).expect("Trying to connect a room that doesn't exist");
+		let mut neighbor = self.map.get(neighbor_loc).expect("Trying to connect to a room that doesn't exist");
+
+		room.set_door(dir, true);
+		neighbor.set_door(dir.opposite(), true);
+
+		self.map.replace(location, room);
+		self.map.replace(neighbor_loc, neighbor);
+	}
+
+	/// Digs a wandering line of rooms from `from` to `to`, connecting up every room along the way
+	pub fn carve_corridor(&mut self, from: Location, to: Location) {
+		let mut current = from;
+
+		while current != to {
+			let distance = current.distance(to);
+			let dir = Direction::iter_all()
+				.filter(|&dir| current.offset_in_direction(dir).distance(to) < distance)
+				.choose(self.rng)
+				.unwrap();
+
+			let next = current.offset_in_direction(dir);
+			self.add_room(next);
+			self.connect(current, dir);
+			current = next;
+		}
+	}
+
+	/// Connects each pair of neighboring rooms that aren't already connected with a chance of `loop_factor`
+	pub fn add_loops(&mut self, loop_factor: f64) {
+		let locations: Vec<Location> = self.map.iter().map(|(loc, _)| loc).collect();
 
-			if !self.map.has(builder_loc) {
-				let mut room = MapBuilder::generate_room();
-				room.set_door(walk_dir.opposite(), true);
-				self.map.add(builder_loc, room);
+		for location in locations {
+			// Only looking one way along each axis, so that each pair only gets the one chance
+			for &dir in [Direction::North, Direction::East].iter() {
+				let room = self.map.get(location).unwrap();
 
-				self.ensure_room_connected(builder_loc);
+				if self.map.has(location.offset_in_direction(dir)) && !room.door(dir) && self.rng.gen_bool(loop_factor) {
+					self.connect(location, dir);
+				}
 			}
 		}
+	}
+
+	/// Opens up one way doors, then digs corridors out to any rooms that can't be reached from the start room.
+	/// That's enough to fix anything `Map::validate` can find, other than problems with the exit itself
+	pub fn repair(&mut self) {
+		let locations: Vec<Location> = self.map.iter().map(|(loc, _)| loc).collect();
+
+		for location in locations {
+			self.ensure_room_connected(location);
+		}
+
+		let start = Location(0, 0);
+
+		loop {
+			let reachable = self.map.reachable_from(start);
+			let unreachable = self.map.iter()
+				.map(|(loc, _)| loc)
+				.find(|loc| !reachable.contains(loc));
+
+			let unreachable = match unreachable {
+				Some(loc) => loc,
+				None => break,
+			};
+
+			// Everything along the way becomes reachable too, so this always makes progress
+			let nearest = reachable.iter()
+				.min_by_key(|loc| loc.distance(unreachable))
+				.cloned()
+				.unwrap();
+
+			self.carve_corridor(nearest, unreachable);
+		}
+	}
+
+	pub fn map(&self) -> &Map { self.map }
+	pub fn rng(&mut self) -> &mut GameRng { self.rng }
+
+	/// Turns a room somewhere other than the start room into the exit, along with the boss guarding it
+	pub fn place_exit(&mut self) {
+		// Generators can come up with nothing but the start room if they're unlucky enough
+		let start = Location(0, 0);
+		if self.map.iter().all(|(loc, _)| loc == start) {
+			let exit_loc = start.offset_in_direction(Direction::North);
+			self.add_room(exit_loc);
+			self.connect(start, Direction::North);
+		}
 
-		// Find a place to spawn the exit and spawn it
 		loop {
-			let (loc, mut room) = self.map.iter().choose(&mut rng()).unwrap();
+			let (loc, mut room) = self.map.iter().choose(self.rng).unwrap();
 			if loc != Location(0, 0) {
-				room.encounter = None; // TODO: boss
+				room.encounter = Some(EncounterType::Boss);
 				room.is_exit = true;
 
 				self.map.replace(loc, room);
@@ -144,4 +402,186 @@ impl<'m> MapBuilder<'m> {
 			}
 		}
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::test_data;
+	use Direction::*;
+
+	/// A map with nothing but `rooms` in it, each with doors in the directions given
+	fn build(rooms: &[(Location, &[Direction])]) -> Map {
+		let mut map = Map { rooms: BTreeMap::new(), visited: BTreeSet::new() };
+
+		for &(location, doors) in rooms {
+			let mut room = Room::new();
+			for &dir in doors {
+				room.set_door(dir, true);
+			}
+
+			map.add(location, room);
+		}
+
+		map
+	}
+
+	fn make_exit(map: &mut Map, location: Location) {
+		let mut room = map.get(location).unwrap();
+		room.is_exit = true;
+		map.replace(location, room);
+	}
+
+	fn add_encounter(map: &mut Map, location: Location, encounter: EncounterType) {
+		let mut room = map.get(location).unwrap();
+		room.encounter = Some(encounter);
+		map.replace(location, room);
+	}
+
+	fn repair(map: &mut Map) {
+		let mut rng = GameRng::seed_from_u64(0);
+		MapBuilder::new(map, &mut rng, test_data().encounters).repair();
+	}
+
+	/// Start room, a corridor east, and the exit north of that
+	fn valid_map() -> Map {
+		let mut map = build(&[
+			(Location(0, 0), &[East]),
+			(Location(1, 0), &[West, North]),
+			(Location(1, 1), &[South]),
+		]);
+
+		make_exit(&mut map, Location(1, 1));
+		map
+	}
+
+	#[test]
+	fn valid_maps_have_no_problems() {
+		let mut map = valid_map();
+		assert_eq!(map.validate(), vec![]);
+
+		let before = format!("{:?}", map);
+		repair(&mut map);
+		assert_eq!(format!("{:?}", map), before);
+	}
+
+	#[test]
+	fn one_way_doors_are_found_and_fixed() {
+		let mut map = valid_map();
+		let mut room = map.get(Location(1, 0)).unwrap();
+		room.set_door(West, false);
+		map.replace(Location(1, 0), room);
+
+		assert_eq!(map.validate(), vec![MapProblem::OneWayDoor(Location(0, 0), East)]);
+
+		repair(&mut map);
+		assert_eq!(map.validate(), vec![]);
+		assert!(map.get(Location(1, 0)).unwrap().door(West));
+	}
+
+	#[test]
+	fn isolated_rooms_are_found_and_fixed() {
+		let mut map = valid_map();
+		map.add(Location(-3, 2), Room::new());
+
+		assert_eq!(map.validate(), vec![
+			MapProblem::Unreachable(Location(-3, 2)),
+			MapProblem::NoDoors(Location(-3, 2)),
+		]);
+
+		repair(&mut map);
+		assert_eq!(map.validate(), vec![]);
+		assert!(map.reachable_from(Location(0, 0)).contains(&Location(-3, 2)));
+	}
+
+	#[test]
+	fn unreachable_exits_are_found_and_fixed() {
+		// The exit's door leads into a room that's never been generated, so there's no way in
+		let mut map = build(&[
+			(Location(0, 0), &[East]),
+			(Location(1, 0), &[West]),
+			(Location(3, 0), &[West]),
+		]);
+
+		make_exit(&mut map, Location(3, 0));
+
+		assert_eq!(map.validate(), vec![MapProblem::UnreachableExit(Location(3, 0))]);
+
+		repair(&mut map);
+		assert_eq!(map.validate(), vec![]);
+		assert!(map.has(Location(2, 0)));
+	}
+
+	#[test]
+	fn problems_with_the_exit_itself_are_found() {
+		let mut map = valid_map();
+		make_exit(&mut map, Location(0, 0));
+		assert_eq!(map.validate(), vec![MapProblem::ExitInStartRoom, MapProblem::ExtraExit(Location(1, 1))]);
+
+		let map = build(&[(Location(0, 0), &[East]), (Location(1, 0), &[West])]);
+		assert_eq!(map.validate(), vec![MapProblem::NoExit]);
+	}
+
+	/// Four rooms in a ring, with the start room in the south west corner
+	fn ring() -> Map {
+		build(&[
+			(Location(0, 0), &[North, East]),
+			(Location(1, 0), &[West, North]),
+			(Location(1, 1), &[South, West]),
+			(Location(0, 1), &[East, South]),
+		])
+	}
+
+	#[test]
+	fn routes_go_through_doors() {
+		// There's a wall between the start room and the room north of it, so the only way there is the long way round
+		let map = build(&[
+			(Location(0, 0), &[East]),
+			(Location(1, 0), &[West, North]),
+			(Location(1, 1), &[South, West]),
+			(Location(0, 1), &[East]),
+		]);
+
+		assert_eq!(map.find_route(Location(0, 0), Location(0, 1), true),
+			Some(vec![Location(0, 0), Location(1, 0), Location(1, 1), Location(0, 1)]));
+
+		assert_eq!(map.find_route(Location(0, 0), Location(0, 0), true), Some(vec![Location(0, 0)]));
+		assert_eq!(map.find_route(Location(0, 0), Location(5, 5), true), None);
+	}
+
+	#[test]
+	fn routes_only_go_through_known_rooms() {
+		let mut map = ring();
+		assert_eq!(map.find_route(Location(0, 0), Location(1, 1), false), None);
+
+		map.mark_visited(Location(1, 0));
+		map.mark_visited(Location(1, 1));
+		assert_eq!(map.find_route(Location(0, 0), Location(1, 1), false),
+			Some(vec![Location(0, 0), Location(1, 0), Location(1, 1)]));
+
+		// With a map, every room is known
+		let map = ring();
+		assert_eq!(map.find_route(Location(0, 0), Location(1, 1), true).map(|route| route.len()), Some(3));
+	}
+
+	#[test]
+	fn routes_avoid_known_dangers() {
+		let mut map = ring();
+		add_encounter(&mut map, Location(0, 1), EncounterType::Monster);
+
+		// Dangers nobody's seen yet don't count
+		assert_eq!(map.find_route(Location(0, 0), Location(1, 1), true).map(|route| route.len()), Some(3));
+
+		map.mark_visited(Location(0, 1));
+		assert_eq!(map.find_route(Location(0, 0), Location(1, 1), true),
+			Some(vec![Location(0, 0), Location(1, 0), Location(1, 1)]));
+
+		add_encounter(&mut map, Location(1, 0), EncounterType::Trap);
+		map.mark_visited(Location(1, 0));
+		assert_eq!(map.find_route(Location(0, 0), Location(1, 1), true), None);
+
+		// Heading straight for a danger is fine though
+		assert_eq!(map.find_route(Location(0, 0), Location(1, 0), true),
+			Some(vec![Location(0, 0), Location(1, 0)]));
+	}
+}
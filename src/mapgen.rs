@@ -0,0 +1,467 @@
+use crate::prelude::*;
+use crate::map::MapBuilder;
+
+use std::collections::BTreeSet;
+
+
+/// Lays out the rooms of a brand new dungeon around the start room at `Location(0, 0)`.
+/// The exit is placed afterwards, so generators only have to worry about rooms and doors
+pub trait MapGenerator {
+	fn generate(&self, builder: &mut MapBuilder<'_>);
+}
+
+
+/// Wanders about from the start room, walking through doors where it can and making new rooms where it can't.
+/// Rooms get random doors, so there's usually more dungeon out past the edges to be found as the player goes
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct RandomWalk {
+	pub steps: usize,
+}
+
+/// Splits an area into smaller and smaller pieces, puts a hall of rooms in each, then joins them back up with corridors
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct BspGrid {
+	/// Roughly how many rooms to make. Corridors make it hard to be exact
+	pub rooms: usize,
+	pub loop_factor: f64,
+}
+
+/// Grows an open, twisty cave out of random noise, keeping only the part that can be reached from the start room
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Cave {
+	/// Roughly how many rooms to make, since it depends on how the noise settles
+	pub rooms: usize,
+	pub loop_factor: f64,
+}
+
+/// A maze grown out from the start room, with some walls knocked through afterwards so there's more than one way around
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct MazeWithLoops {
+	pub rooms: usize,
+	pub loop_factor: f64,
+
+	/// 0 grows long winding passages, 1 grows lots of short dead ends
+	pub branchiness: f64,
+}
+
+
+impl Default for RandomWalk {
+	fn default() -> Self { RandomWalk { steps: 50 } }
+}
+
+impl Default for BspGrid {
+	fn default() -> Self { BspGrid { rooms: 30, loop_factor: 0.1 } }
+}
+
+impl Default for Cave {
+	fn default() -> Self { Cave { rooms: 40, loop_factor: 0.4 } }
+}
+
+impl Default for MazeWithLoops {
+	fn default() -> Self { MazeWithLoops { rooms: 30, loop_factor: 0.1, branchiness: 0.5 } }
+}
+
+
+impl MapGenerator for RandomWalk {
+	fn generate(&self, builder: &mut MapBuilder<'_>) {
+		let mut builder_loc = Location(0, 0);
+
+		for _ in 0..self.steps {
+			// Walk through a door if possible, otherwise just pick a direction and pretend there's a door there
+			let walk_dir = if let Some(room) = builder.map().get(builder_loc) {
+				room.iter_neighbor_directions().choose(builder.rng())
+					.unwrap_or_else(|| builder.rng().gen())
+			} else {
+				builder.rng().gen()
+			};
+
+			builder_loc = builder_loc.offset_in_direction(walk_dir);
+			builder.add_random_room(builder_loc, walk_dir.opposite());
+		}
+	}
+}
+
+
+impl MapGenerator for BspGrid {
+	fn generate(&self, builder: &mut MapBuilder<'_>) {
+		// Halls only fill part of each piece, so there needs to be space for more rooms than were asked for
+		let size = ((self.rooms * 3 / 2) as f64).sqrt().ceil().max(3.0) as i32;
+		let corner = Location(-size / 2, -size / 2);
+
+		let hall = self.split(builder, corner, size, size);
+
+		// The start room isn't necessarily in a hall, or on a corridor
+		let start = Location(0, 0);
+		if builder.map().get(start).unwrap().iter_neighbor_directions().next().is_none() {
+			builder.carve_corridor(start, hall);
+		}
+
+		builder.add_loops(self.loop_factor);
+	}
+}
+
+impl BspGrid {
+	/// Pieces this small or smaller get a hall of their own instead of being split further
+	const MAX_PIECE_SIZE: i32 = 4;
+
+	/// Fills the piece with its corner at `corner` with halls and corridors, and returns the location of one of its rooms
+	fn split(&self, builder: &mut MapBuilder<'_>, corner: Location, width: i32, height: i32) -> Location {
+		if width <= Self::MAX_PIECE_SIZE && height <= Self::MAX_PIECE_SIZE {
+			return self.carve_hall(builder, corner, width, height);
+		}
+
+		// Always split the long way, so pieces stay roughly square
+		let (first, second) = if width >= height {
+			let at = builder.rng().gen_range(2, width - 1);
+			(
+				self.split(builder, corner, at, height),
+				self.split(builder, corner.offset(at, 0), width - at, height),
+			)
+		} else {
+			let at = builder.rng().gen_range(2, height - 1);
+			(
+				self.split(builder, corner, width, at),
+				self.split(builder, corner.offset(0, at), width, height - at),
+			)
+		};
+
+		builder.carve_corridor(first, second);
+
+		if builder.rng().gen() { first } else { second }
+	}
+
+	fn carve_hall(&self, builder: &mut MapBuilder<'_>, corner: Location, width: i32, height: i32) -> Location {
+		let rng = builder.rng();
+		let hall_width = rng.gen_range(1, width + 1);
+		let hall_height = rng.gen_range(1, height + 1);
+		let hall_corner = corner.offset(rng.gen_range(0, width - hall_width + 1), rng.gen_range(0, height - hall_height + 1));
+
+		for x in 0..hall_width {
+			for y in 0..hall_height {
+				builder.add_room(hall_corner.offset(x, y));
+			}
+		}
+
+		// Halls are wide open
+		for x in 0..hall_width {
+			for y in 0..hall_height {
+				let location = hall_corner.offset(x, y);
+				if x + 1 < hall_width { builder.connect(location, Direction::East) }
+				if y + 1 < hall_height { builder.connect(location, Direction::North) }
+			}
+		}
+
+		let rng = builder.rng();
+		hall_corner.offset(rng.gen_range(0, hall_width), rng.gen_range(0, hall_height))
+	}
+}
+
+
+impl MapGenerator for Cave {
+	fn generate(&self, builder: &mut MapBuilder<'_>) {
+		// Smoothing leaves a bit under half of the area open, and not all of that is reachable
+		let size = ((self.rooms * 5 / 2) as f64).sqrt().ceil().max(3.0) as i32;
+		let corner = Location(-size / 2, -size / 2);
+
+		// Noise can settle into a tiny pocket around the start room, in which case it's worth another go
+		let mut cave = BTreeSet::new();
+		for _ in 0..10 {
+			cave = Self::grow_cave(builder.rng(), corner, size);
+			if cave.len() >= self.rooms / 2 {
+				break
+			}
+		}
+
+		for &location in cave.iter() {
+			builder.add_room(location);
+		}
+
+		// Join everything up with a random spanning tree before adding loops, so that it's all reachable
+		let mut connected: BTreeSet<Location> = [Location(0, 0)].iter().cloned().collect();
+		let mut frontier = vec![Location(0, 0)];
+
+		while !frontier.is_empty() {
+			let index = builder.rng().gen_range(0, frontier.len());
+			let location = frontier[index];
+
+			let next = Direction::iter_all()
+				.filter(|&dir| {
+					let neighbor = location.offset_in_direction(dir);
+					cave.contains(&neighbor) && !connected.contains(&neighbor)
+				})
+				.choose(builder.rng());
+
+			match next {
+				Some(dir) => {
+					let neighbor = location.offset_in_direction(dir);
+					builder.connect(location, dir);
+					connected.insert(neighbor);
+					frontier.push(neighbor);
+				}
+
+				None => { frontier.swap_remove(index); }
+			}
+		}
+
+		builder.add_loops(self.loop_factor);
+	}
+}
+
+impl Cave {
+	/// Runs a cellular automaton over a square of noise, and returns the open space connected to the start room
+	fn grow_cave(rng: &mut GameRng, corner: Location, size: i32) -> BTreeSet<Location> {
+		let index = |Location(x, y): Location| {
+			let (x, y) = (x - corner.0, y - corner.1);
+			if x < 0 || y < 0 || x >= size || y >= size {
+				None
+			} else {
+				Some((x + y * size) as usize)
+			}
+		};
+
+		let mut open: Vec<bool> = (0..size * size).map(|_| rng.gen_bool(0.55)).collect();
+
+		for _ in 0..4 {
+			let previous = open.clone();
+
+			for y in 0..size {
+				for x in 0..size {
+					let location = corner.offset(x, y);
+
+					// Anything outside the area counts as wall
+					let walls = (-1..=1)
+						.flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+						.filter(|&(dx, dy)| (dx, dy) != (0, 0))
+						.filter(|&(dx, dy)| !index(location.offset(dx, dy)).map_or(false, |i| previous[i]))
+						.count();
+
+					let i = index(location).unwrap();
+					if walls > 4 {
+						open[i] = false;
+					} else if walls < 4 {
+						open[i] = true;
+					}
+				}
+			}
+		}
+
+		let start = Location(0, 0);
+		open[index(start).unwrap()] = true;
+
+		let mut cave: BTreeSet<Location> = [start].iter().cloned().collect();
+		let mut queue = vec![start];
+
+		while let Some(location) = queue.pop() {
+			for dir in Direction::iter_all() {
+				let neighbor = location.offset_in_direction(dir);
+				let is_open = index(neighbor).map_or(false, |i| open[i]);
+
+				if is_open && cave.insert(neighbor) {
+					queue.push(neighbor);
+				}
+			}
+		}
+
+		cave
+	}
+}
+
+
+impl MapGenerator for MazeWithLoops {
+	fn generate(&self, builder: &mut MapBuilder<'_>) {
+		// Kept to a square with some space to spare, so the maze doesn't wander off in a long line
+		let half_size = (((self.rooms * 3 / 2) as f64).sqrt() / 2.0).ceil() as i32;
+		let in_bounds = |Location(x, y): Location| x.abs() <= half_size && y.abs() <= half_size;
+
+		let mut carved = 1;
+		let mut active = vec![Location(0, 0)];
+
+		while carved < self.rooms && !active.is_empty() {
+			// Growing from the newest room makes long passages, growing from any room makes branches
+			let index = if builder.rng().gen_bool(self.branchiness) {
+				builder.rng().gen_range(0, active.len())
+			} else {
+				active.len() - 1
+			};
+
+			let location = active[index];
+			let options: Vec<Direction> = Direction::iter_all()
+				.filter(|&dir| {
+					let neighbor = location.offset_in_direction(dir);
+					in_bounds(neighbor) && !builder.map().has(neighbor)
+				})
+				.collect();
+
+			let next = options.choose(builder.rng()).copied();
+
+			match next {
+				Some(dir) => {
+					let neighbor = location.offset_in_direction(dir);
+					builder.add_room(neighbor);
+					builder.connect(location, dir);
+
+					carved += 1;
+					active.push(neighbor);
+				}
+
+				None => { active.remove(index); }
+			}
+		}
+
+		builder.add_loops(self.loop_factor);
+	}
+}
+
+
+/// Every generator, along with its settings. This is what gets picked on the command line and recorded in replays
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum GeneratorSettings {
+	RandomWalk(RandomWalk),
+	Bsp(BspGrid),
+	Cave(Cave),
+	Maze(MazeWithLoops),
+}
+
+impl GeneratorSettings {
+	pub const NAMES: [&'static str; 4] = ["walk", "bsp", "cave", "maze"];
+
+	/// The generator called `name`, with its default settings
+	pub fn named(name: &str) -> Option<GeneratorSettings> {
+		match name {
+			"walk" => Some(GeneratorSettings::RandomWalk(RandomWalk::default())),
+			"bsp" => Some(GeneratorSettings::Bsp(BspGrid::default())),
+			"cave" => Some(GeneratorSettings::Cave(Cave::default())),
+			"maze" => Some(GeneratorSettings::Maze(MazeWithLoops::default())),
+			_ => None,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			GeneratorSettings::RandomWalk(_) => "walk",
+			GeneratorSettings::Bsp(_) => "bsp",
+			GeneratorSettings::Cave(_) => "cave",
+			GeneratorSettings::Maze(_) => "maze",
+		}
+	}
+
+	/// Checks for settings that would either panic or leave nowhere to put the exit
+	pub fn validate(&self) -> Result<(), String> {
+		let mut problems = Vec::new();
+
+		let (rooms, loop_factor, branchiness) = match *self {
+			GeneratorSettings::RandomWalk(RandomWalk { steps }) => (steps + 1, 0.0, 0.0),
+			GeneratorSettings::Bsp(BspGrid { rooms, loop_factor }) => (rooms, loop_factor, 0.0),
+			GeneratorSettings::Cave(Cave { rooms, loop_factor }) => (rooms, loop_factor, 0.0),
+			GeneratorSettings::Maze(MazeWithLoops { rooms, loop_factor, branchiness }) => (rooms, loop_factor, branchiness),
+		};
+
+		if rooms < 2 {
+			problems.push("There needs to be room for at least one room besides the start room".to_owned());
+		}
+
+		if !(0.0..=1.0).contains(&loop_factor) {
+			problems.push(format!("A loop factor of {} isn't between 0 and 1", loop_factor));
+		}
+
+		if !(0.0..=1.0).contains(&branchiness) {
+			problems.push(format!("A branchiness of {} isn't between 0 and 1", branchiness));
+		}
+
+		if problems.is_empty() {
+			Ok(())
+		} else {
+			Err(problems.join(", "))
+		}
+	}
+}
+
+impl Default for GeneratorSettings {
+	fn default() -> Self { GeneratorSettings::RandomWalk(RandomWalk::default()) }
+}
+
+impl MapGenerator for GeneratorSettings {
+	fn generate(&self, builder: &mut MapBuilder<'_>) {
+		match self {
+			GeneratorSettings::RandomWalk(generator) => generator.generate(builder),
+			GeneratorSettings::Bsp(generator) => generator.generate(builder),
+			GeneratorSettings::Cave(generator) => generator.generate(builder),
+			GeneratorSettings::Maze(generator) => generator.generate(builder),
+		}
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::map::Map;
+	use crate::room::EncounterTables;
+	use crate::gamestate::test_data;
+
+	/// Generates a map with `generator` and places the exit, without repairing anything
+	fn generate(generator: GeneratorSettings, seed: u64, encounters: &'static EncounterTables) -> Map {
+		let mut map = Map::new();
+		let mut rng = GameRng::seed_from_u64(seed);
+
+		let mut builder = MapBuilder::new(&mut map, &mut rng, encounters);
+		generator.generate(&mut builder);
+		builder.place_exit();
+
+		map
+	}
+
+	fn assert_generates_valid_maps(generator: GeneratorSettings) {
+		assert!(generator.validate().is_ok(), "{:?}", generator);
+
+		let encounters = test_data().encounters;
+
+		for seed in 0..50 {
+			let map = generate(generator, seed, encounters);
+			let problems = map.validate();
+			assert!(problems.is_empty(), "{:?} with seed {} made a map with {:?}", generator, seed, problems);
+		}
+	}
+
+	#[test]
+	fn every_generator_makes_valid_maps() {
+		for name in GeneratorSettings::NAMES.iter() {
+			let generator = GeneratorSettings::named(name).unwrap();
+			assert_eq!(generator.name(), *name);
+			assert_generates_valid_maps(generator);
+		}
+	}
+
+	#[test]
+	fn the_fewest_rooms_still_make_valid_maps() {
+		for &loop_factor in &[0.0, 1.0] {
+			assert_generates_valid_maps(GeneratorSettings::Bsp(BspGrid { rooms: 2, loop_factor }));
+			assert_generates_valid_maps(GeneratorSettings::Cave(Cave { rooms: 2, loop_factor }));
+			assert_generates_valid_maps(GeneratorSettings::Maze(MazeWithLoops { rooms: 2, loop_factor, branchiness: 0.5 }));
+		}
+
+		assert_generates_valid_maps(GeneratorSettings::RandomWalk(RandomWalk { steps: 1 }));
+	}
+
+	#[test]
+	fn any_loop_factor_makes_valid_maps() {
+		for &loop_factor in &[0.0, 1.0] {
+			assert_generates_valid_maps(GeneratorSettings::Bsp(BspGrid { loop_factor, .. BspGrid::default() }));
+			assert_generates_valid_maps(GeneratorSettings::Cave(Cave { loop_factor, .. Cave::default() }));
+
+			for &branchiness in &[0.0, 1.0] {
+				assert_generates_valid_maps(GeneratorSettings::Maze(MazeWithLoops { loop_factor, branchiness, .. MazeWithLoops::default() }));
+			}
+		}
+	}
+
+	#[test]
+	fn settings_out_of_range_are_refused() {
+		assert!(GeneratorSettings::Bsp(BspGrid { rooms: 1, loop_factor: 0.1 }).validate().is_err());
+		assert!(GeneratorSettings::Cave(Cave { rooms: 40, loop_factor: 1.5 }).validate().is_err());
+		assert!(GeneratorSettings::Maze(MazeWithLoops { rooms: 30, loop_factor: 0.1, branchiness: -0.1 }).validate().is_err());
+		assert!(GeneratorSettings::RandomWalk(RandomWalk { steps: 0 }).validate().is_err());
+		assert!(GeneratorSettings::named("nonsense").is_none());
+	}
+}
@@ -1,4 +1,6 @@
-pub use rand::{Rng, random, seq::SliceRandom, seq::IteratorRandom};
+pub use rand::{Rng, SeedableRng, random, seq::SliceRandom, seq::IteratorRandom};
+
+pub use serde::{Serialize, Deserialize};
 
 pub use common::math::*;
 pub use common::easing::*;
@@ -9,21 +11,21 @@ pub use std::future::Future;
 pub use std::error::Error;
 
 pub use crate::types::*;
-pub use crate::get_executor;
 
 // pub type Result<T> = std::result::Result<T, failure::Error>;
 
-pub fn rng() -> rand::rngs::ThreadRng { rand::thread_rng() }
+/// The only source of randomness game logic should use, so that a run can be reproduced from its seed
+pub type GameRng = rand::rngs::StdRng;
 
 
-pub fn choose_with_weights<T: Copy>(values: &[T], weights: &[i32]) -> T {
+pub fn choose_with_weights<T: Copy>(values: &[T], weights: &[i32], rng: &mut impl Rng) -> T {
 	use rand::distributions::weighted::WeightedIndex;
 	use rand::distributions::Distribution;
 
 	assert!(values.len() == weights.len());
 
 	let dist = WeightedIndex::new(weights).unwrap();
-	values[dist.sample(&mut rng())]
+	values[dist.sample(rng)]
 }
 
-pub type GameStateHandle = Rc<RefCell<crate::gamestate::GameState>>;
+pub type RngHandle = Rc<RefCell<GameRng>>;
@@ -0,0 +1,213 @@
+use crate::prelude::*;
+use crate::gamestate::GameState;
+use crate::mapgen::GeneratorSettings;
+use crate::item::ItemCatalog;
+use crate::task::{PlayerCommand, UntypedPromise};
+use crate::view::{View, ViewCommand};
+
+use std::fs::File;
+use std::io::{Write, BufRead, BufReader};
+use std::path::Path;
+
+/// Bump this whenever `PlayerCommand`, `GeneratorSettings` or anything they hold changes shape.
+/// Old replays aren't migrated, since they only reproduce anything on the version of the game that recorded them
+pub const REPLAY_VERSION: u32 = 6;
+
+
+/// Replays are written one entry per line as the game goes, so that whatever led up to a crash
+/// is already on disk when it happens
+#[derive(Debug, Serialize, Deserialize)]
+enum ReplayEntry {
+	Start { version: u32, seed: u64, generator: GeneratorSettings },
+	Command(PlayerCommand),
+
+	/// A `save::snapshot` of the state the game finished in
+	Finish { state: String },
+}
+
+
+pub struct Recorder {
+	file: File,
+}
+
+impl Recorder {
+	pub fn create(path: impl AsRef<Path>, seed: u64, generator: GeneratorSettings) -> Result<Recorder, Box<dyn Error>> {
+		let mut recorder = Recorder { file: File::create(path)? };
+		recorder.write(&ReplayEntry::Start { version: REPLAY_VERSION, seed, generator })?;
+		Ok(recorder)
+	}
+
+	pub fn record_command(&mut self, command: &PlayerCommand) -> Result<(), Box<dyn Error>> {
+		self.write(&ReplayEntry::Command(command.clone()))
+	}
+
+	pub fn finish(mut self, state: &GameState) -> Result<(), Box<dyn Error>> {
+		let state = crate::save::snapshot(state)?;
+		self.write(&ReplayEntry::Finish { state })
+	}
+
+	fn write(&mut self, entry: &ReplayEntry) -> Result<(), Box<dyn Error>> {
+		writeln!(self.file, "{}", ron::ser::to_string(entry)?)?;
+		self.file.flush()?;
+		Ok(())
+	}
+}
+
+
+#[derive(Debug)]
+pub struct Replay {
+	pub seed: u64,
+	pub generator: GeneratorSettings,
+	commands: Vec<PlayerCommand>,
+	final_state: Option<String>,
+}
+
+impl Replay {
+	/// Any equipment in the replay is looked up in `catalog`
+	pub fn load_from_file(path: impl AsRef<Path>, catalog: &'static ItemCatalog) -> Result<Replay, Box<dyn Error>> {
+		let reader = BufReader::new(File::open(path)?);
+		let mut lines = reader.lines();
+
+		let (seed, generator) = match ron::de::from_str(&lines.next().ok_or("Replay is empty")??)? {
+			ReplayEntry::Start { version, seed, generator } if version == REPLAY_VERSION => (seed, generator),
+			ReplayEntry::Start { version, .. } => {
+				return Err(format!("Replay version {} doesn't match supported version {}", version, REPLAY_VERSION).into());
+			}
+
+			_ => return Err("Replay doesn't start with a Start entry".into()),
+		};
+
+		let mut replay = Replay {
+			seed,
+			generator,
+			commands: Vec::new(),
+			final_state: None,
+		};
+
+		for line in lines {
+			let line = line?;
+
+			match catalog.deserialize_with(|| ron::de::from_str(&line))? {
+				ReplayEntry::Command(command) => replay.commands.push(command),
+				ReplayEntry::Finish { state } => replay.final_state = Some(state),
+				ReplayEntry::Start { .. } => return Err("Replay has more than one Start entry".into()),
+			}
+		}
+
+		Ok(replay)
+	}
+
+	/// Replays cut short by a crash or by loading a save have nothing to check against, so they fail too
+	pub fn check_final_state(&self, state: &GameState) -> Result<(), Box<dyn Error>> {
+		let expected = self.final_state.as_ref()
+			.ok_or("Replay has no recorded final state to check against, it was either cut short or stopped at a load")?;
+
+		if crate::save::snapshot(state)? != *expected {
+			return Err("Final state doesn't match the recorded final state".into());
+		}
+
+		Ok(())
+	}
+}
+
+
+/// Wraps another view, answering `GetPlayerCommand`s from a replay instead of asking the player.
+/// Quits once the replay runs out
+pub struct ReplayView<V: View> {
+	inner: V,
+	commands: std::vec::IntoIter<PlayerCommand>,
+	should_quit: bool,
+}
+
+impl<V: View> ReplayView<V> {
+	pub fn new(inner: V, replay: &Replay) -> ReplayView<V> {
+		ReplayView {
+			inner,
+			commands: replay.commands.clone().into_iter(),
+			should_quit: false,
+		}
+	}
+}
+
+impl<V: View> View for ReplayView<V> {
+	fn submit_command(&mut self, cmd: ViewCommand, promise: UntypedPromise) {
+		match cmd {
+			ViewCommand::GetPlayerCommand => match self.commands.next() {
+				Some(command) => {
+					self.inner.on_replayed_player_command(&command);
+					promise.player_command().fulfill(command);
+				}
+
+				None => self.should_quit = true,
+			}
+
+			cmd => self.inner.submit_command(cmd, promise),
+		}
+	}
+
+	fn init(&mut self, gamestate: &GameState) { self.inner.init(gamestate) }
+	fn update(&mut self, gamestate: &GameState) { self.inner.update(gamestate) }
+	fn should_quit(&self) -> bool { self.should_quit || self.inner.should_quit() }
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::test_data;
+	use crate::session::Session;
+	use crate::view::ScriptedView;
+
+	fn new_game(seed: u64) -> GameState {
+		let mut state = GameState::new(seed, test_data());
+		state.generate_map();
+		state
+	}
+
+	/// Plays `script` through to the end, recording it to a file named after the test
+	fn record(name: &str, seed: u64, script: &[&str]) -> (Replay, GameState) {
+		let path = std::env::temp_dir().join(format!("rogue-lewis-{}.ron", name));
+		let state = new_game(seed);
+		let recorder = Recorder::create(&path, seed, state.data.generator).unwrap();
+
+		let lines: Vec<String> = script.iter().map(|&line| line.to_owned()).collect();
+		let mut session = Session::new(ScriptedView::from_lines(lines, state.data.catalog), state, Some(recorder), false);
+		while session.step() {}
+		let (_, state) = session.finish();
+
+		let replay = Replay::load_from_file(&path, state.data.catalog).unwrap();
+		std::fs::remove_file(&path).unwrap();
+
+		(replay, state)
+	}
+
+	#[test]
+	fn replays_reproduce_the_game() {
+		let script = ["n", "e", "s", "w", "n", "n", "heal", "i", "f", "f", "f", "f", "e", "e", "q"];
+
+		for seed in 0..5 {
+			let (replay, recorded_state) = record("reproduce", seed, &script);
+			assert_eq!(replay.seed, seed);
+			assert!(replay.final_state.is_some());
+
+			let view = ReplayView::new(ScriptedView::from_lines(Vec::new(), recorded_state.data.catalog), &replay);
+			let mut session = Session::new(view, new_game(replay.seed), None, false);
+			while session.step() {}
+			let (_, replayed_state) = session.finish();
+
+			replay.check_final_state(&replayed_state).unwrap();
+		}
+	}
+
+	#[test]
+	fn recording_stops_before_loading() {
+		let (replay, state) = record("load", 1, &["i", "m", "load", "i", "q"]);
+
+		let commands: Vec<String> = replay.commands.iter().map(|command| format!("{:?}", command)).collect();
+		assert_eq!(commands, ["Main(ShowInventory)", "Main(ShowMap)"]);
+
+		// With nothing to check against, the replay can't be verified
+		assert!(replay.final_state.is_none());
+		assert!(replay.check_final_state(&state).is_err());
+	}
+}
@@ -1,7 +1,11 @@
 use crate::prelude::*;
-use rand::distributions::{Standard, Distribution};
 
-#[derive(Debug, Copy, Clone)]
+use crate::data::DataFile;
+
+pub const DEFAULT_ENCOUNTERS_PATH: &str = "data/encounters.ron";
+
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Room {
 	pub doors: [bool; 4],
 	pub encounter: Option<EncounterType>,
@@ -34,7 +38,7 @@ impl Room {
 }
 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum EncounterType {
 	Food,
 	Treasure,
@@ -51,21 +55,6 @@ pub enum EncounterType {
 }
 
 impl EncounterType {
-	pub fn probability(&self) -> f32 {
-		match self {
-			EncounterType::Food 	=> 33.0,
-			EncounterType::Treasure => 10.0,
-			EncounterType::Key 		=> 2.6,
-			EncounterType::Map 		=> 2.0,
-			EncounterType::Equipment=> 4.0,
-			EncounterType::Merchant => 3.3,
-			EncounterType::Chest 	=> 3.0,
-			EncounterType::Trap 	=> 5.0,
-			EncounterType::Monster 	=> 13.0,
-			EncounterType::Boss 	=> 1.0,
-		}
-	}
-
 	/// Determines whether this encounter hangs around after the player
 	/// enters the room or if it's consumed immediately
 	pub fn is_persistent(&self) -> bool {
@@ -81,22 +70,101 @@ impl EncounterType {
 	}
 }
 
-impl Distribution<EncounterType> for Standard {
-	fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> EncounterType {
-		let choices = [
-			EncounterType::Food,
-			EncounterType::Treasure,
-			EncounterType::Key,
-			EncounterType::Map,
-			EncounterType::Merchant,
-			EncounterType::Equipment,
-			EncounterType::Chest,
-			EncounterType::Trap,
-			EncounterType::Monster,
-			EncounterType::Boss,
-		];
-
-		*choices.choose_weighted(rng, EncounterType::probability).unwrap()
+
+/// Which encounters turn up in newly generated rooms, from some distance away from the start room onwards
+#[derive(Debug, Deserialize)]
+pub struct EncounterTable {
+	/// How many rooms away from the start room, ignoring walls, this table starts being used
+	pub min_distance: i32,
+
+	/// How likely a room is to have an encounter at all
+	pub encounter_chance: f64,
+
+	/// How often each encounter turns up relative to the others. Anything left out never does
+	pub weights: Vec<(EncounterType, f32)>,
+}
+
+/// Every encounter table, ordered by distance
+#[derive(Debug)]
+pub struct EncounterTables {
+	tables: Vec<EncounterTable>,
+}
+
+impl DataFile for EncounterTables {
+	type Contents = Vec<EncounterTable>;
+	const NAME: &'static str = "encounter tables";
+
+	fn from_contents(tables: Vec<EncounterTable>) -> EncounterTables {
+		EncounterTables { tables }
+	}
+
+	fn validate(&self) -> Vec<String> {
+		let mut problems = Vec::new();
+
+		match self.tables.first() {
+			Some(table) if table.min_distance != 0 => {
+				problems.push(format!("The first table starts at distance {}, but there needs to be one for the start room at distance 0", table.min_distance));
+			}
+
+			None => problems.push("There needs to be at least one table".to_owned()),
+			_ => {}
+		}
+
+		for (index, table) in self.tables.iter().enumerate() {
+			let distance = table.min_distance;
+
+			if index > 0 && self.tables[index-1].min_distance >= distance {
+				problems.push(format!("The table at distance {} needs to start further out than the one before it", distance));
+			}
+
+			if !(0.0..=1.0).contains(&table.encounter_chance) {
+				problems.push(format!("The table at distance {} has an encounter chance of {}, which isn't between 0 and 1", distance, table.encounter_chance));
+			}
+
+			for (weight_index, &(encounter, weight)) in table.weights.iter().enumerate() {
+				if table.weights[..weight_index].iter().any(|&(other, _)| other == encounter) {
+					problems.push(format!("The table at distance {} has more than one weight for {:?}", distance, encounter));
+				}
+
+				if !weight.is_finite() || weight < 0.0 {
+					problems.push(format!("The table at distance {} has a weight of {} for {:?}, which can't be negative", distance, weight, encounter));
+				}
+			}
+
+			let total_weight: f32 = table.weights.iter()
+				.map(|&(_, weight)| weight)
+				.filter(|weight| weight.is_finite() && *weight > 0.0)
+				.sum();
+
+			if table.encounter_chance > 0.0 && total_weight <= 0.0 {
+				problems.push(format!("The table at distance {} has a chance of encounters, but nothing with a weight above zero", distance));
+			}
+		}
+
+		problems
 	}
 }
 
+impl EncounterTables {
+	/// The furthest out table that starts at or before `location`
+	pub fn table_at(&self, location: Location) -> &EncounterTable {
+		let distance = location.distance(Location(0, 0));
+
+		self.tables.iter()
+			.rev()
+			.find(|table| table.min_distance <= distance)
+			.unwrap()
+	}
+
+	pub fn choose(&self, location: Location, rng: &mut impl Rng) -> Option<EncounterType> {
+		let table = self.table_at(location);
+
+		if !rng.gen_bool(table.encounter_chance) {
+			return None
+		}
+
+		table.weights.choose_weighted(rng, |&(_, weight)| weight)
+			.ok()
+			.map(|&(encounter, _)| encounter)
+	}
+}
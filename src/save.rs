@@ -0,0 +1,311 @@
+use crate::prelude::*;
+use crate::gamestate::{GameState, Player};
+use crate::map::Map;
+use crate::enemy::Enemy;
+use crate::trap::TrapKind;
+use crate::item::{Equipment, ItemCatalog};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Bump this whenever the layout of `SaveFile` changes, and teach `migrate` how to upgrade
+/// from the previous version
+pub const SAVE_VERSION: u32 = 6;
+
+pub const DEFAULT_SAVE_PATH: &str = "save.ron";
+
+
+/// Enemies are written out as they are, but only read back in as far as the name of their archetype.
+/// See `SavedEnemy`
+#[derive(Debug, Serialize, Deserialize)]
+struct SaveFile<E = Enemy> {
+	version: u32,
+
+	seed: u64,
+	rng_seed: u64,
+
+	map: Map,
+	player: Player,
+	enemies: BTreeMap<Location, E>,
+
+	#[serde(default)]
+	traps: BTreeMap<Location, TrapKind>,
+
+	#[serde(default)]
+	enemies_killed: usize,
+
+	#[serde(default)]
+	bosses_killed: usize,
+
+	#[serde(default)]
+	turns: usize,
+}
+
+/// An enemy as read from a save, before its archetype has been looked up in the bestiary
+#[derive(Debug, Deserialize)]
+struct SavedEnemy {
+	archetype: VariantName,
+	health: i32,
+}
+
+/// Just enough of a save file to figure out how to read the rest of it
+#[derive(Debug, Deserialize)]
+struct SaveHeader {
+	version: u32,
+}
+
+
+impl SaveFile {
+	fn new(state: &GameState, rng_seed: u64) -> SaveFile {
+		SaveFile {
+			version: SAVE_VERSION,
+
+			seed: state.seed,
+			rng_seed,
+
+			map: state.map.clone(),
+			player: state.player.clone(),
+			enemies: state.enemies.clone(),
+			traps: state.traps.clone(),
+			enemies_killed: state.enemies_killed,
+			bosses_killed: state.bosses_killed,
+			turns: state.turns,
+		}
+	}
+}
+
+
+pub fn save_to_file(state: &GameState, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+	// Reseed so that a game continued after saving plays out the same as one loaded from the save
+	let rng_seed = state.rng.borrow_mut().gen();
+	*state.rng.borrow_mut() = GameRng::seed_from_u64(rng_seed);
+
+	let save = SaveFile::new(state, rng_seed);
+
+	let contents = ron::ser::to_string_pretty(&save, Default::default())?;
+	std::fs::write(path, contents)?;
+
+	Ok(())
+}
+
+
+/// Serializes everything a save file would hold, except for the state of the rng, without touching `state`.
+/// Two games that have played out the same will produce the same snapshot
+pub fn snapshot(state: &GameState) -> Result<String, Box<dyn Error>> {
+	let contents = ron::ser::to_string_pretty(&SaveFile::new(state, 0), Default::default())?;
+	Ok(contents)
+}
+
+
+/// Replaces `state` with the contents of a save file.
+/// `state.rng` is reseeded in place rather than replaced, so existing handles to it remain valid
+pub fn load_from_file(state: &mut GameState, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+	let contents = std::fs::read_to_string(path)?;
+	load_from_str(state, &contents)
+}
+
+/// Like `load_from_file`, but with the contents of the save file already in hand
+pub fn load_from_str(state: &mut GameState, contents: &str) -> Result<(), Box<dyn Error>> {
+	let SaveHeader { version } = ron::de::from_str(&contents)?;
+	if version > SAVE_VERSION {
+		return Err(format!("Save file version {} is newer than supported version {}", version, SAVE_VERSION).into());
+	}
+
+	// Equipment that isn't in the catalog is refused here
+	let mut save: SaveFile<SavedEnemy> = state.data.catalog.deserialize_with(|| ron::de::from_str(contents))?;
+
+	migrate(&mut save, version, state.data.catalog);
+
+	let mut enemies = BTreeMap::new();
+
+	for (loc, SavedEnemy { archetype, health }) in save.enemies {
+		let archetype = state.data.bestiary.find(&archetype.0)
+			.ok_or_else(|| format!("Save file has a {} in it, which isn't in the enemy definitions", archetype.0))?;
+
+		enemies.insert(loc, Enemy { archetype, health });
+	}
+
+	state.seed = save.seed;
+	*state.rng.borrow_mut() = GameRng::seed_from_u64(save.rng_seed);
+
+	state.map = save.map;
+	state.player = save.player;
+	state.enemies = enemies;
+	state.traps = save.traps;
+
+	// Saves are only ever made mid-game, so the game being loaded can't have been won yet,
+	// even if the one it replaces was
+	state.escaped = false;
+
+	state.enemies_killed = save.enemies_killed;
+	state.bosses_killed = save.bosses_killed;
+	state.turns = save.turns;
+
+	Ok(())
+}
+
+
+/// Upgrades a save written by an older version of the game.
+/// Fields added since `from_version` should be marked `#[serde(default)]` so that older files
+/// still deserialize, and any fixups they need beyond that go here
+fn migrate(save: &mut SaveFile<SavedEnemy>, from_version: u32, catalog: &ItemCatalog) {
+	assert!(from_version <= SAVE_VERSION);
+
+	// v2 added `traps`. A trap's kind is only decided once the player first walks into it,
+	// so trap rooms from a v1 save can go without one until then
+
+	// v3 added equipment slots. Before then every piece of equipment carried counted towards
+	// the player's stats, so the closest we can get is to equip the best of each
+	if from_version < 3 {
+		let mut equipment: Vec<Equipment> = save.player.inventory.iter_equipment().collect();
+		equipment.sort_by_key(|&e| -(e.attack() + e.defense()));
+
+		for e in equipment {
+			if save.player.equipped(e.slot()).is_none() {
+				save.player.equip(catalog, e);
+			}
+		}
+	}
+
+	// v4 added stack limits, so older saves may be carrying more than they're now allowed
+	if from_version < 4 {
+		save.player.inventory.enforce_stack_limits(catalog);
+	}
+
+	// v5 started counting kills. There's no telling how many there were before then, so older saves start from zero
+
+	// v6 started counting bosses killed and turns taken, which likewise start from zero
+
+	save.version = SAVE_VERSION;
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::test_data;
+	use crate::item::{Item, EquipSlot};
+
+	/// A save from before traps, equipment slots, stack limits and kill counts, carrying two maps
+	/// and more equipment than it has slots for
+	const V1_SAVE: &str = r#"(
+		version: 1,
+		seed: 42,
+		rng_seed: 7,
+		map: (
+			rooms: {
+				(0, 0): (doors: (true, true, false, false), encounter: None, is_exit: false),
+				(0, 1): (doors: (false, false, true, false), encounter: None, is_exit: true),
+				(1, 0): (doors: (false, false, false, true), encounter: Some(Monster), is_exit: false),
+			},
+			visited: [(0, 0), (1, 0)],
+		),
+		player: (
+			location: (0, 0),
+			health: 12,
+			hunger: 8,
+			inventory: (
+				items: [Map, Equipment(Sword), Map, Equipment(Shield), Equipment(Axe), Equipment(Sword)],
+				treasure: 3,
+				food: 9,
+			),
+		),
+		enemies: {
+			(1, 0): (archetype: Orc, health: 4),
+		},
+	)"#;
+
+	fn equipment(name: &str) -> Equipment {
+		test_data().catalog.find_equipment(name).unwrap()
+	}
+
+	#[test]
+	fn v1_save_migrates() {
+		let mut state = GameState::new(0, test_data());
+		load_from_str(&mut state, V1_SAVE).unwrap();
+
+		assert_eq!(state.seed, 42);
+		assert_eq!(state.player.health, 12);
+		assert_eq!(state.player.hunger, 8);
+		assert_eq!(state.player.inventory.count(Item::Food), 9);
+		assert_eq!(state.player.inventory.count(Item::Treasure), 3);
+
+		// The best of each slot is equipped, and everything else stays in the inventory
+		assert_eq!(state.player.equipped(EquipSlot::Weapon), Some(equipment("Axe")));
+		assert_eq!(state.player.equipped(EquipSlot::Offhand), Some(equipment("Shield")));
+		assert_eq!(state.player.equipped(EquipSlot::Armour), None);
+		assert_eq!(state.player.inventory.count(Item::Equipment(equipment("Sword"))), 2);
+		assert_eq!(state.player.inventory.count(Item::Equipment(equipment("Axe"))), 0);
+
+		// Only one map can be carried, so the other becomes surplus
+		assert_eq!(state.player.inventory.count(Item::Map), 1);
+		assert_eq!(state.player.inventory.count_surplus(Item::Map), 1);
+
+		let orc = state.get_enemy(Location(1, 0)).unwrap();
+		assert_eq!(format!("{:?}", orc.archetype), "Orc");
+		assert_eq!(orc.health, 4);
+
+		assert!(state.traps.is_empty());
+		assert_eq!(state.enemies_killed, 0);
+		assert_eq!(state.bosses_killed, 0);
+		assert_eq!(state.turns, 0);
+	}
+
+	#[test]
+	fn v3_save_keeps_its_equipment() {
+		let save = V1_SAVE
+			.replace("version: 1", "version: 3")
+			.replace("hunger: 8,", "hunger: 8, equipped: (Some(Sword), None, None),");
+
+		let mut state = GameState::new(0, test_data());
+		load_from_str(&mut state, &save).unwrap();
+
+		// Slots were already around by v3, so nothing gets equipped on the player's behalf
+		assert_eq!(state.player.equipped(EquipSlot::Weapon), Some(equipment("Sword")));
+		assert_eq!(state.player.equipped(EquipSlot::Offhand), None);
+		assert_eq!(state.player.inventory.count(Item::Equipment(equipment("Axe"))), 1);
+
+		// But stack limits came later
+		assert_eq!(state.player.inventory.count(Item::Map), 1);
+		assert_eq!(state.player.inventory.count_surplus(Item::Map), 1);
+	}
+
+	#[test]
+	fn saves_load_back_the_same() {
+		let data = test_data();
+
+		let mut state = GameState::new(9, data);
+		state.generate_map();
+		state.player.inventory.add(data.catalog, Item::Equipment(equipment("Axe")));
+		state.player.equip(data.catalog, equipment("Axe"));
+		state.spawn_enemy_at(Location(0, 0), true);
+
+		let saved = snapshot(&state).unwrap();
+
+		let mut loaded = GameState::new(0, data);
+		load_from_str(&mut loaded, &saved).unwrap();
+
+		assert_eq!(snapshot(&loaded).unwrap(), saved);
+	}
+
+	#[test]
+	fn loading_after_escaping_starts_over() {
+		let mut state = GameState::new(0, test_data());
+		state.escaped = true;
+
+		load_from_str(&mut state, V1_SAVE).unwrap();
+		assert!(!state.escaped);
+	}
+
+	#[test]
+	fn newer_saves_are_refused() {
+		let save = V1_SAVE.replace("version: 1", &format!("version: {}", SAVE_VERSION + 1));
+		assert!(load_from_str(&mut GameState::new(0, test_data()), &save).is_err());
+	}
+
+	#[test]
+	fn unknown_items_are_refused() {
+		let save = V1_SAVE.replace("Equipment(Shield)", "Equipment(Mace)");
+		assert!(load_from_str(&mut GameState::new(0, test_data()), &save).is_err());
+	}
+}
@@ -0,0 +1,154 @@
+use crate::prelude::*;
+use crate::gamestate::GameSummary;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub const DEFAULT_SCORES_PATH: &str = "scores.ron";
+
+/// Only the best few runs for each seed are worth remembering
+const SCORES_KEPT_PER_SEED: usize = 10;
+
+const TREASURE_POINTS: u32 = 10;
+const ROOM_POINTS: u32 = 5;
+const ENEMY_POINTS: u32 = 20;
+const BOSS_POINTS: u32 = 100;
+const TURN_POINTS: u32 = 1;
+
+
+/// Bosses are worth `BOSS_POINTS` in place of `ENEMY_POINTS`, rather than on top of it
+pub fn calculate(summary: &GameSummary) -> u32 {
+	let monsters_killed = summary.enemies_killed - summary.bosses_killed;
+
+	summary.treasure as u32 * TREASURE_POINTS
+		+ summary.rooms_explored as u32 * ROOM_POINTS
+		+ monsters_killed as u32 * ENEMY_POINTS
+		+ summary.bosses_killed as u32 * BOSS_POINTS
+		+ summary.turns as u32 * TURN_POINTS
+}
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreEntry {
+	/// Kept as it was when the run finished, so that runs from before and after a change to scoring can be compared
+	pub score: u32,
+	pub summary: GameSummary,
+}
+
+/// The best runs on each seed, best first
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HighScores {
+	by_seed: BTreeMap<u64, Vec<ScoreEntry>>,
+}
+
+impl HighScores {
+	/// A missing file is just an empty table
+	pub fn load_from_file(path: impl AsRef<Path>) -> Result<HighScores, Box<dyn Error>> {
+		let path = path.as_ref();
+		if !path.exists() {
+			return Ok(HighScores::default())
+		}
+
+		let contents = std::fs::read_to_string(path)?;
+		Ok(ron::de::from_str(&contents)?)
+	}
+
+	pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+		let contents = ron::ser::to_string_pretty(self, Default::default())?;
+		std::fs::write(path, contents)?;
+		Ok(())
+	}
+
+	/// Returns where the run placed among others on the same seed, counting from 1,
+	/// or `None` if it wasn't good enough to make the table
+	pub fn add(&mut self, summary: GameSummary) -> Option<usize> {
+		let score = calculate(&summary);
+		let entries = self.by_seed.entry(summary.seed).or_insert_with(Vec::new);
+
+		// Ties go to whoever got there first
+		let rank = entries.iter().position(|e| e.score < score).unwrap_or(entries.len());
+		if rank >= SCORES_KEPT_PER_SEED {
+			return None
+		}
+
+		entries.insert(rank, ScoreEntry { score, summary });
+		entries.truncate(SCORES_KEPT_PER_SEED);
+
+		Some(rank + 1)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item=(u64, &[ScoreEntry])> + '_ {
+		self.by_seed.iter().map(|(&seed, entries)| (seed, &entries[..]))
+	}
+}
+
+
+/// Adds finished games to the high score table at `path`
+pub struct ScoreKeeper {
+	path: String,
+}
+
+impl ScoreKeeper {
+	pub fn new(path: &str) -> ScoreKeeper {
+		ScoreKeeper { path: path.to_owned() }
+	}
+
+	/// Returns where the run placed among others on the same seed, like `HighScores::add`.
+	/// A table that can't be read or written is reported and otherwise ignored
+	pub fn record(&self, summary: GameSummary) -> Option<usize> {
+		let result = HighScores::load_from_file(&self.path)
+			.and_then(|mut scores| {
+				let rank = scores.add(summary);
+				scores.save_to_file(&self.path)?;
+				Ok(rank)
+			});
+
+		result.unwrap_or_else(|err| {
+			eprintln!("Failed to record score in '{}': {}", self.path, err);
+			None
+		})
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::GameOutcome;
+
+	fn summary(seed: u64, treasure: usize) -> GameSummary {
+		GameSummary {
+			seed,
+			outcome: GameOutcome::Defeat,
+			rooms_explored: 1,
+			enemies_killed: 0,
+			bosses_killed: 0,
+			treasure,
+			turns: 0,
+		}
+	}
+
+	#[test]
+	fn score_keeper_returns_the_rank() {
+		let path = std::env::temp_dir().join("rogue-lewis-scores.ron");
+		let _ = std::fs::remove_file(&path);
+
+		let keeper = ScoreKeeper::new(path.to_str().unwrap());
+		assert_eq!(keeper.record(summary(1, 5)), Some(1));
+		assert_eq!(keeper.record(summary(1, 10)), Some(1));
+		assert_eq!(keeper.record(summary(1, 5)), Some(3));
+		assert_eq!(keeper.record(summary(2, 0)), Some(1));
+
+		// Only the best few on each seed are kept
+		for _ in 0..SCORES_KEPT_PER_SEED {
+			keeper.record(summary(1, 20));
+		}
+		assert_eq!(keeper.record(summary(1, 5)), None);
+
+		let scores = HighScores::load_from_file(&path).unwrap();
+		let counts: Vec<(u64, usize)> = scores.iter().map(|(seed, entries)| (seed, entries.len())).collect();
+		assert_eq!(counts, vec![(1, SCORES_KEPT_PER_SEED), (2, 1)]);
+
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,92 @@
+use crate::prelude::*;
+use crate::gamestate::GameState;
+use crate::view::View;
+use crate::task::{Executor, ControllerContext, JoinHandle};
+use crate::replay::Recorder;
+use crate::score::ScoreKeeper;
+use crate::event::EventSubscriber;
+use crate::controller;
+
+use std::time::Instant;
+
+
+/// A single game and everything needed to run it. Sessions don't share anything, so any number
+/// of them can be stepped side by side
+pub struct Session<V: View> {
+	gamestate: GameState,
+	view: V,
+
+	executor: Executor,
+	ctx: ControllerContext,
+	main_task: JoinHandle<()>,
+	last_step: Instant,
+}
+
+
+impl<V: View> Session<V> {
+	pub fn new(mut view: V, gamestate: GameState, recorder: Option<Recorder>, realtime: bool) -> Session<V> {
+		let executor = Executor::new();
+		let ctx = ControllerContext::new(executor.clone(), Rc::clone(&gamestate.rng), gamestate.data.catalog, realtime);
+
+		if let Some(recorder) = recorder {
+			ctx.set_recorder(recorder);
+		}
+
+		let main_task = executor.queue(controller::run_main_controller(ctx.clone()));
+
+		view.init(&gamestate);
+
+		Session {
+			gamestate,
+			view,
+
+			executor,
+			ctx,
+			main_task,
+			last_step: Instant::now(),
+		}
+	}
+
+	/// `subscriber` hears about everything that happens in this game from here on
+	pub fn subscribe(&self, subscriber: impl EventSubscriber + 'static) {
+		self.ctx.subscribe(subscriber);
+	}
+
+	/// Every game played in this session from here on goes on `score_keeper`'s high score table
+	pub fn keep_score(&self, score_keeper: ScoreKeeper) {
+		self.ctx.set_score_keeper(score_keeper);
+	}
+
+	pub fn is_running(&self) -> bool {
+		!self.main_task.is_finished() && !self.view.should_quit()
+	}
+
+	/// Runs a single tick of the game, and returns whether it's still running afterwards.
+	/// Does nothing once the game is over
+	pub fn step(&mut self) -> bool {
+		if !self.is_running() {
+			return false
+		}
+
+		let now = Instant::now();
+		self.executor.advance_clock(now - self.last_step);
+		self.last_step = now;
+
+		self.executor.resume_tasks();
+		self.executor.process_commands(&mut self.gamestate, &mut self.view);
+		self.view.update(&self.gamestate);
+
+		self.is_running()
+	}
+
+	/// Finishes off any recording, and hands back the view and the state the game ended in
+	pub fn finish(self) -> (V, GameState) {
+		if let Some(recorder) = self.ctx.take_recorder() {
+			if let Err(err) = recorder.finish(&self.gamestate) {
+				eprintln!("Failed to finish recording: {}", err);
+			}
+		}
+
+		(self.view, self.gamestate)
+	}
+}
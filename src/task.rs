@@ -1,26 +1,42 @@
 pub mod executor;
 pub mod promise;
+pub mod select;
+mod clock;
 
-pub use promise::{UntypedPromise, Promise, Promisable, FutureValue};
-pub use executor::Executor;
+pub use promise::{UntypedPromise, Promise, Promisable};
+pub use executor::{Executor, JoinHandle};
+pub use select::race;
 
 use crate::prelude::*;
 
 use crate::view::ViewCommand;
-use crate::gamestate::GameCommand;
+use crate::gamestate::{GameCommand, HealthModifyReason, Player, PlayerStats, GameSummary};
+use crate::item::{Item, Equipment, EquipSlot, ItemCatalog};
+use crate::map::Map;
+use crate::room::Room;
+use crate::enemy::Enemy;
+use crate::trap::TrapKind;
+use crate::replay::Recorder;
+use crate::score::ScoreKeeper;
+use crate::event::{EventBus, EventSubscriber};
+
+use std::time::Duration;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ControllerMode {
-	Main, Battle, Merchant
+	Main, Battle, Merchant, Disarm, Equip, GameOver
 }
 
-use crate::controller::{main, battle, merchant};
+use crate::controller::{main, battle, merchant, disarm, equip, game_over};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PlayerCommand {
 	Main(main::PlayerCommand),
 	Battle(battle::PlayerCommand),
 	Merchant(merchant::PlayerCommand),
+	Disarm(disarm::PlayerCommand),
+	Equip(equip::PlayerCommand),
+	GameOver(game_over::PlayerCommand),
 	Debug(Vec<String>),
 }
 
@@ -46,6 +62,27 @@ impl PlayerCommand {
 		}
 	}
 
+	pub fn disarm(&self) -> Option<&disarm::PlayerCommand> {
+		match self {
+			PlayerCommand::Disarm(cmd) => Some(cmd),
+			_ => None,
+		}
+	}
+
+	pub fn equip(&self) -> Option<&equip::PlayerCommand> {
+		match self {
+			PlayerCommand::Equip(cmd) => Some(cmd),
+			_ => None,
+		}
+	}
+
+	pub fn game_over(&self) -> Option<&game_over::PlayerCommand> {
+		match self {
+			PlayerCommand::GameOver(cmd) => Some(cmd),
+			_ => None,
+		}
+	}
+
 	pub fn debug(&self) -> Option<&[String]> {
 		match self {
 			PlayerCommand::Debug(cmd) => Some(cmd),
@@ -66,118 +103,331 @@ impl From<merchant::PlayerCommand> for PlayerCommand {
 	fn from(cmd: merchant::PlayerCommand) -> PlayerCommand { PlayerCommand::Merchant(cmd) }
 }
 
+impl From<disarm::PlayerCommand> for PlayerCommand {
+	fn from(cmd: disarm::PlayerCommand) -> PlayerCommand { PlayerCommand::Disarm(cmd) }
+}
 
-
-pub async fn enter_mode(mode: ControllerMode) {
-	get_executor()
-		.schedule_view_command(ViewCommand::PushControllerMode(mode))
-		.await
+impl From<equip::PlayerCommand> for PlayerCommand {
+	fn from(cmd: equip::PlayerCommand) -> PlayerCommand { PlayerCommand::Equip(cmd) }
 }
 
-pub async fn leave_mode() {
-	get_executor()
-		.schedule_view_command(ViewCommand::PopControllerMode)
-		.await
+impl From<game_over::PlayerCommand> for PlayerCommand {
+	fn from(cmd: game_over::PlayerCommand) -> PlayerCommand { PlayerCommand::GameOver(cmd) }
 }
 
 
-pub async fn get_player_command() -> PlayerCommand {
-	get_executor()
-		.schedule_view_command(ViewCommand::GetPlayerCommand)
-		.await
-}
 
-pub async fn show_map(whole_map: bool) {
-	get_executor()
-		.schedule_view_command(ViewCommand::ShowMap {whole_map})
-		.await
-}
+/// Everything a controller needs to drive the game. Controllers only get at the game through the
+/// commands this schedules, so all changes to it go through `GameState::submit_command`
+#[derive(Clone)]
+pub struct ControllerContext {
+	executor: Executor,
 
-pub async fn show_inventory() {
-	get_executor()
-		.schedule_view_command(ViewCommand::ShowInventory)
-		.await
-}
+	/// Shared with the `GameState`, so that the controllers and the model draw from the same sequence
+	rng: RngHandle,
 
-use crate::gamestate::HealthModifyReason;
-use crate::item::Item;
+	/// Also the `GameState`'s. It never changes, so there's no need to ask the model for it
+	catalog: &'static ItemCatalog,
 
-// TODO: consume/interact_room_encounter/item?
+	recorder: Rc<RefCell<Option<Recorder>>>,
+	score_keeper: Rc<RefCell<Option<ScoreKeeper>>>,
+	event_bus: Rc<RefCell<EventBus>>,
 
-pub async fn give_player_item(item: Item) {
-	give_player_item_n(item, 1).await
+	/// Whether the player is kept under time pressure. When they are, what happens depends on how quickly
+	/// they react, so the game can't be replayed
+	realtime: bool,
 }
 
-pub async fn give_player_item_n(item: Item, n: usize) {
-	let command = GameCommand::GivePlayerItem(item, n);
-	get_executor().schedule_model_command::<()>(command).await;
-	get_executor().schedule_view_command(ViewCommand::GameCommand(command)).await
-}
+impl ControllerContext {
+	pub fn new(executor: Executor, rng: RngHandle, catalog: &'static ItemCatalog, realtime: bool) -> ControllerContext {
+		ControllerContext {
+			executor,
+			rng,
+			catalog,
+			recorder: Rc::new(RefCell::new(None)),
+			score_keeper: Rc::new(RefCell::new(None)),
+			event_bus: Rc::new(RefCell::new(EventBus::new())),
+			realtime,
+		}
+	}
 
-pub async fn consume_player_item(item: Item) -> bool {
-	consume_player_item_n(item, 1).await
-}
+	pub fn rng(&self) -> std::cell::RefMut<'_, GameRng> { self.rng.borrow_mut() }
+	pub fn catalog(&self) -> &'static ItemCatalog { self.catalog }
+
+	pub fn set_recorder(&self, recorder: Recorder) { *self.recorder.borrow_mut() = Some(recorder); }
+	pub fn take_recorder(&self) -> Option<Recorder> { self.recorder.borrow_mut().take() }
+
+	pub fn set_score_keeper(&self, score_keeper: ScoreKeeper) { *self.score_keeper.borrow_mut() = Some(score_keeper); }
+
+	/// Returns where the game placed on the high score table, or `None` if it didn't make it
+	/// or nobody's keeping score
+	pub fn record_score(&self, summary: GameSummary) -> Option<usize> {
+		self.score_keeper.borrow().as_ref().and_then(|keeper| keeper.record(summary))
+	}
 
-pub async fn consume_player_item_n(item: Item, n: usize) -> bool {
-	let command = GameCommand::ConsumePlayerItem(item, n);
+	pub fn subscribe(&self, subscriber: impl EventSubscriber + 'static) { self.event_bus.borrow_mut().subscribe(subscriber); }
+
+	/// Stops recording if the replay can't be written to, rather than leaving a replay with holes in it.
+	/// Recording also stops just short of loading a save, since a replay has no way of knowing what was in it
+	fn record_player_command(&self, command: &PlayerCommand) {
+		let mut recorder = self.recorder.borrow_mut();
+
+		if recorder.is_some() && matches!(command, PlayerCommand::Main(main::PlayerCommand::Load)) {
+			eprintln!("Loaded games can't be replayed, so recording stops here");
+			*recorder = None;
+			return
+		}
+
+		if let Some(Err(err)) = recorder.as_mut().map(|r| r.record_command(command)) {
+			eprintln!("Failed to record player command, recording stopped: {}", err);
+			*recorder = None;
+		}
+	}
+
+
+	/// Runs a command that changes the game, then publishes whatever events it caused
+	async fn run_model_command<O: Promisable>(&self, command: GameCommand) -> O {
+		let result = self.executor.schedule_model_command(command).await;
+		self.publish_events().await;
+		result
+	}
 
-	let success = get_executor().schedule_model_command(command).await;
-	if success {
-		// TODO: maybe I want an event on failure and success?
-		// or maybe I want something more specific than just forwarding the GameCommand
-		get_executor()
-			.schedule_view_command(ViewCommand::GameCommand(command))
+	/// Hands out events to every subscriber before passing them on to the view. The view gets to hold things up
+	/// while it shows each one, e.g. until the player has finished walking into a room
+	async fn publish_events(&self) {
+		for event in self.executor.take_events() {
+			self.event_bus.borrow_mut().publish(&event);
+			self.executor.schedule_view_command::<()>(ViewCommand::GameEvent(event)).await;
+		}
+	}
+
+
+	pub async fn enter_mode(&self, mode: ControllerMode) {
+		self.executor
+			.schedule_view_command(ViewCommand::PushControllerMode(mode))
 			.await
 	}
 
-	success
-}
+	pub async fn leave_mode(&self) {
+		self.executor
+			.schedule_view_command(ViewCommand::PopControllerMode)
+			.await
+	}
 
-pub async fn heal_player(n: u32) {
-	let command = GameCommand::ModifyPlayerHealth(n as i32, HealthModifyReason::Heal);
-	get_executor().schedule_model_command::<bool>(command).await;
-	get_executor().schedule_view_command(ViewCommand::GameCommand(command)).await
-}
 
-pub async fn damage_player(n: u32, reason: HealthModifyReason) -> bool {
-	let command = GameCommand::ModifyPlayerHealth(-(n as i32), reason);
-	let still_alive = get_executor().schedule_model_command(command).await;
-	// TODO: pass status to view
-	get_executor().schedule_view_command::<()>(ViewCommand::GameCommand(command)).await;
-	still_alive
-}
+	pub async fn get_player_command(&self) -> PlayerCommand {
+		let command = self.executor
+			.schedule_view_command(ViewCommand::GetPlayerCommand)
+			.await;
 
-pub async fn starve_player() -> bool {
-	let command = GameCommand::StarvePlayer;
-	let still_alive = get_executor().schedule_model_command(command).await;
-	// TODO: pass status to view
-	get_executor().schedule_view_command::<()>(ViewCommand::GameCommand(command)).await;
-	still_alive
-}
+		self.record_player_command(&command);
+		command
+	}
 
-pub async fn sate_player() {
-	let command = GameCommand::SatePlayer;
-	get_executor().schedule_model_command::<()>(command).await;
-	// TODO: pass status to view
-	get_executor().schedule_view_command(ViewCommand::GameCommand(command)).await
-}
+	/// Like `get_player_command`, except that in a realtime game the player only has so long to make up their mind.
+	/// Returns `None` if they run out of time
+	pub async fn get_player_command_within(&self, patience: Duration) -> Option<PlayerCommand> {
+		if !self.realtime {
+			return Some(self.get_player_command().await)
+		}
 
-pub async fn move_player(dir: Direction) -> bool {
-	let command = GameCommand::MovePlayer(dir);
-	let did_move = get_executor().schedule_model_command(command).await;
-	if did_move {
-		// TODO: pass success to view
-		get_executor().schedule_view_command::<()>(ViewCommand::GameCommand(command)).await;
+		race(
+			async { Some(self.get_player_command().await) },
+			async {
+				self.executor.sleep(patience).await;
+				None
+			}
+		).await
+	}
+
+	pub async fn show_map(&self, whole_map: bool) {
+		self.executor
+			.schedule_view_command(ViewCommand::ShowMap {whole_map})
+			.await
+	}
+
+	/// Shows the route the player is about to take, as found by `Map::find_route`
+	pub async fn show_route(&self, route: Vec<Location>) {
+		self.executor
+			.schedule_view_command(ViewCommand::ShowRoute { route })
+			.await
+	}
+
+	pub async fn show_inventory(&self) {
+		self.executor
+			.schedule_view_command(ViewCommand::ShowInventory)
+			.await
+	}
+
+	pub async fn show_game_over(&self, summary: GameSummary, score: u32, rank: Option<usize>) {
+		self.executor
+			.schedule_view_command(ViewCommand::ShowGameOver { summary, score, rank })
+			.await
+	}
+
+	// TODO: consume/interact_room_encounter/item?
+
+	pub async fn give_player_item(&self, item: Item) {
+		self.give_player_item_n(item, 1).await
+	}
+
+	/// Anything the player can't carry is kept as surplus
+	pub async fn give_player_item_n(&self, item: Item, n: usize) {
+		self.run_model_command::<usize>(GameCommand::GivePlayerItem(item, n)).await;
+	}
+
+	pub async fn consume_player_item(&self, item: Item) -> bool {
+		self.consume_player_item_n(item, 1).await
+	}
+
+	pub async fn consume_player_item_n(&self, item: Item, n: usize) -> bool {
+		self.run_model_command(GameCommand::ConsumePlayerItem(item, n)).await
+	}
+
+	pub async fn consume_player_surplus_item(&self, item: Item) -> bool {
+		self.run_model_command(GameCommand::ConsumePlayerSurplusItem(item, 1)).await
+	}
+
+	pub async fn heal_player(&self, n: u32) {
+		self.run_model_command::<bool>(GameCommand::ModifyPlayerHealth(n as i32, HealthModifyReason::Heal)).await;
+	}
+
+	pub async fn damage_player(&self, n: u32, reason: HealthModifyReason) -> bool {
+		self.run_model_command(GameCommand::ModifyPlayerHealth(-(n as i32), reason)).await
 	}
-	did_move
-}
 
+	pub async fn consume_potion(&self) -> bool {
+		self.run_model_command(GameCommand::ConsumePotion).await
+	}
+
+	pub async fn starve_player(&self) -> bool {
+		self.run_model_command(GameCommand::StarvePlayer).await
+	}
+
+	pub async fn sate_player(&self) {
+		self.run_model_command(GameCommand::SatePlayer).await
+	}
 
+	pub async fn move_player(&self, dir: Direction) -> bool {
+		self.run_model_command(GameCommand::MovePlayer(dir)).await
+	}
+
+
+
+	pub async fn teleport_player(&self, location: Location) {
+		self.run_model_command(GameCommand::TeleportPlayer(location)).await
+	}
+
+	pub async fn drop_player_item(&self, item: Item) -> bool {
+		self.run_model_command(GameCommand::DropPlayerItem(item, 1)).await
+	}
+
+	pub async fn equip_item(&self, equipment: Equipment) -> bool {
+		self.run_model_command(GameCommand::EquipItem(equipment)).await
+	}
 
-// Battle
+	pub async fn unequip_item(&self, slot: EquipSlot) -> bool {
+		self.run_model_command(GameCommand::UnequipItem(slot)).await
+	}
+
+	pub async fn disarm_trap(&self, location: Location) {
+		self.run_model_command(GameCommand::DisarmTrap(location)).await
+	}
+
+	pub async fn escape_dungeon(&self) {
+		self.run_model_command(GameCommand::EscapeDungeon).await
+	}
+
+	pub async fn save_game(&self) -> bool {
+		self.run_model_command(GameCommand::SaveGame).await
+	}
+
+	pub async fn load_game(&self) -> bool {
+		self.run_model_command(GameCommand::LoadGame).await
+	}
+
+	pub async fn end_game(&self) {
+		self.run_model_command(GameCommand::EndGame).await
+	}
+
+	pub async fn restart_game(&self) {
+		self.run_model_command(GameCommand::Restart).await
+	}
+
+
+
+	// Encounters
+
+	pub async fn spawn_enemy(&self, location: Location, boss: bool) {
+		self.run_model_command(GameCommand::SpawnEnemy(location, boss)).await
+	}
 
-pub async fn attack_enemy(damage: i32) {
-	let command = GameCommand::AttackEnemy(damage);
-	get_executor().schedule_model_command::<()>(command).await;
-	get_executor().schedule_view_command(ViewCommand::GameCommand(command)).await
-}
\ No newline at end of file
+	pub async fn spawn_trap(&self, location: Location) {
+		self.run_model_command(GameCommand::SpawnTrap(location)).await
+	}
+
+	pub async fn remove_trap(&self, location: Location) {
+		self.run_model_command(GameCommand::RemoveTrap(location)).await
+	}
+
+	pub async fn remove_encounter(&self, location: Location) {
+		self.run_model_command(GameCommand::RemoveEncounter(location)).await
+	}
+
+	pub async fn replace_room(&self, location: Location, room: Room) {
+		self.run_model_command(GameCommand::ReplaceRoom(location, room)).await
+	}
+
+	pub async fn mark_visited(&self, location: Location) {
+		self.run_model_command(GameCommand::MarkVisited(location)).await
+	}
+
+
+
+	// Queries
+
+	pub async fn get_player(&self) -> Player {
+		self.executor.schedule_model_command(GameCommand::GetPlayer).await
+	}
+
+	pub async fn get_player_stats(&self) -> PlayerStats {
+		self.executor.schedule_model_command(GameCommand::GetPlayerStats).await
+	}
+
+	pub async fn get_map(&self) -> Map {
+		self.executor.schedule_model_command(GameCommand::GetMap).await
+	}
+
+	pub async fn get_room(&self, location: Location) -> Option<Room> {
+		self.executor.schedule_model_command(GameCommand::GetRoom(location)).await
+	}
+
+	pub async fn get_enemy(&self, location: Location) -> Option<Enemy> {
+		self.executor.schedule_model_command(GameCommand::GetEnemy(location)).await
+	}
+
+	pub async fn get_trap(&self, location: Location) -> Option<TrapKind> {
+		self.executor.schedule_model_command(GameCommand::GetTrap(location)).await
+	}
+
+	pub async fn has_escaped(&self) -> bool {
+		self.executor.schedule_model_command(GameCommand::HasEscaped).await
+	}
+
+	pub async fn get_summary(&self) -> GameSummary {
+		self.executor.schedule_model_command(GameCommand::GetSummary).await
+	}
+
+	/// A debug dump of the whole game
+	pub async fn describe_state(&self) -> String {
+		self.executor.schedule_model_command(GameCommand::DescribeState).await
+	}
+
+
+
+	// Battle
+
+	pub async fn attack_enemy(&self, damage: i32) {
+		self.run_model_command(GameCommand::AttackEnemy(damage)).await
+	}
+}
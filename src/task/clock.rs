@@ -0,0 +1,200 @@
+use std::task::{Waker, Poll};
+use std::time::Duration;
+use std::pin::Pin;
+use std::collections::BTreeMap;
+
+use crate::prelude::*;
+
+
+/// Time as far as tasks are concerned. It only moves when the executor is told that a frame has passed,
+/// so every task sees the same time for the whole of a frame
+pub(super) struct FrameClock {
+	now: Duration,
+	dt: Duration,
+	frame: u64,
+
+	/// Keyed by which `NextFrame` or `Sleep` is waiting, so that polling one again replaces its waker
+	/// rather than adding another, and dropping it can take it back out
+	frame_waiters: BTreeMap<WaiterId, Waker>,
+	sleepers: BTreeMap<WaiterId, (Duration, Waker)>,
+	next_waiter_id: WaiterId,
+}
+
+type WaiterId = u64;
+
+
+impl FrameClock {
+	pub(super) fn new() -> FrameClock {
+		FrameClock {
+			now: Duration::from_secs(0),
+			dt: Duration::from_secs(0),
+			frame: 0,
+
+			frame_waiters: BTreeMap::new(),
+			sleepers: BTreeMap::new(),
+			next_waiter_id: 0,
+		}
+	}
+
+	fn new_waiter_id(&mut self) -> WaiterId {
+		self.next_waiter_id += 1;
+		self.next_waiter_id
+	}
+
+	/// Returns the wakers of everything that was waiting on this frame
+	pub(super) fn advance(&mut self, dt: Duration) -> Vec<Waker> {
+		self.now += dt;
+		self.dt = dt;
+		self.frame += 1;
+
+		let now = self.now;
+		let (woken, sleeping): (BTreeMap<_, _>, BTreeMap<_, _>) = std::mem::replace(&mut self.sleepers, BTreeMap::new())
+			.into_iter()
+			.partition(|&(_, (deadline, _))| deadline <= now);
+
+		self.sleepers = sleeping;
+
+		std::mem::replace(&mut self.frame_waiters, BTreeMap::new())
+			.into_iter()
+			.map(|(_, waker)| waker)
+			.chain(woken.into_iter().map(|(_, (_, waker))| waker))
+			.collect()
+	}
+}
+
+
+/// Resolves on the next frame to how long that frame took, in seconds
+pub(super) struct NextFrame {
+	clock: Rc<RefCell<FrameClock>>,
+	frame: u64,
+	id: WaiterId,
+}
+
+impl NextFrame {
+	pub(super) fn new(clock: Rc<RefCell<FrameClock>>) -> NextFrame {
+		let (frame, id) = {
+			let mut clock = clock.borrow_mut();
+			(clock.frame, clock.new_waiter_id())
+		};
+
+		NextFrame { clock, frame, id }
+	}
+}
+
+impl Future for NextFrame {
+	type Output = f32;
+
+	fn poll(self: Pin<&mut Self>, ctx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+		let mut clock = self.clock.borrow_mut();
+
+		if clock.frame > self.frame {
+			Poll::Ready(clock.dt.as_secs_f32())
+		} else {
+			clock.frame_waiters.insert(self.id, ctx.waker().clone());
+			Poll::Pending
+		}
+	}
+}
+
+impl Drop for NextFrame {
+	fn drop(&mut self) {
+		self.clock.borrow_mut().frame_waiters.remove(&self.id);
+	}
+}
+
+
+pub(super) struct Sleep {
+	clock: Rc<RefCell<FrameClock>>,
+	deadline: Duration,
+	id: WaiterId,
+}
+
+impl Sleep {
+	pub(super) fn new(clock: Rc<RefCell<FrameClock>>, duration: Duration) -> Sleep {
+		let (deadline, id) = {
+			let mut clock = clock.borrow_mut();
+			(clock.now + duration, clock.new_waiter_id())
+		};
+
+		Sleep { clock, deadline, id }
+	}
+}
+
+impl Future for Sleep {
+	type Output = ();
+
+	fn poll(self: Pin<&mut Self>, ctx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+		let mut clock = self.clock.borrow_mut();
+
+		if clock.now >= self.deadline {
+			Poll::Ready(())
+		} else {
+			clock.sleepers.insert(self.id, (self.deadline, ctx.waker().clone()));
+			Poll::Pending
+		}
+	}
+}
+
+/// A sleep that loses a race stops waiting there and then, rather than hanging around until its deadline
+impl Drop for Sleep {
+	fn drop(&mut self) {
+		self.clock.borrow_mut().sleepers.remove(&self.id);
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::task::{Context, RawWaker, RawWakerVTable};
+
+	fn noop_waker() -> Waker {
+		fn clone(_: *const ()) -> RawWaker { RawWaker::new(std::ptr::null(), &VTABLE) }
+		fn noop(_: *const ()) {}
+
+		static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+		unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+	}
+
+	fn poll<F: Future>(future: &mut Pin<Box<F>>) -> Poll<F::Output> {
+		let waker = noop_waker();
+		future.as_mut().poll(&mut Context::from_waker(&waker))
+	}
+
+	#[test]
+	fn waiters_only_register_once() {
+		let clock = Rc::new(RefCell::new(FrameClock::new()));
+		let mut sleep = Box::pin(Sleep::new(Rc::clone(&clock), Duration::from_secs(1)));
+		let mut next_frame = Box::pin(NextFrame::new(Rc::clone(&clock)));
+
+		for _ in 0..3 {
+			assert!(poll(&mut sleep).is_pending());
+			assert!(poll(&mut next_frame).is_pending());
+		}
+
+		assert_eq!(clock.borrow().sleepers.len(), 1);
+		assert_eq!(clock.borrow().frame_waiters.len(), 1);
+
+		assert_eq!(clock.borrow_mut().advance(Duration::from_millis(100)).len(), 1);
+		assert_eq!(poll(&mut next_frame), Poll::Ready(0.1));
+		assert!(poll(&mut sleep).is_pending());
+		assert_eq!(clock.borrow().sleepers.len(), 1);
+	}
+
+	#[test]
+	fn dropped_waiters_stop_waiting() {
+		let clock = Rc::new(RefCell::new(FrameClock::new()));
+		let mut sleep = Box::pin(Sleep::new(Rc::clone(&clock), Duration::from_secs(1)));
+		let mut next_frame = Box::pin(NextFrame::new(Rc::clone(&clock)));
+
+		assert!(poll(&mut sleep).is_pending());
+		assert!(poll(&mut next_frame).is_pending());
+
+		drop(sleep);
+		drop(next_frame);
+
+		assert!(clock.borrow().sleepers.is_empty());
+		assert!(clock.borrow().frame_waiters.is_empty());
+		assert!(clock.borrow_mut().advance(Duration::from_secs(2)).is_empty());
+	}
+}
@@ -2,15 +2,21 @@ use std::task::{self, RawWaker, RawWakerVTable, Waker, Poll};
 use std::sync::{Arc, Mutex};
 use std::future::Future;
 use std::pin::Pin;
+use std::time::Duration;
 
 use crate::prelude::*;
 use super::promise::*;
+use super::clock::{FrameClock, NextFrame, Sleep};
 use crate::view::{View, ViewCommand};
 use crate::gamestate::{GameState, GameCommand};
+use crate::event::GameEvent;
 
 pub(super) struct CommandQueues {
 	pub model_commands: Vec<(GameCommand, UntypedPromise)>,
 	pub view_commands: Vec<(ViewCommand, UntypedPromise)>,
+
+	/// Events emitted by the model while running commands, waiting to be published
+	pub events: Vec<GameEvent>,
 }
 
 struct TaskList {
@@ -18,19 +24,22 @@ struct TaskList {
 	task_id: usize,
 }
 
+/// Clones share the same tasks and command queues
+#[derive(Clone)]
 pub struct Executor {
 	task_list: Arc<Mutex<TaskList>>,
 	wake_queue: Arc<Mutex<Vec<TaskId>>>,
 
 	command_queues: Rc<RefCell<CommandQueues>>,
-	hack_gamestate: GameStateHandle,
+	clock: Rc<RefCell<FrameClock>>,
 }
 
 impl Executor {
-	pub fn new(hack_gamestate: GameStateHandle) -> Self {
+	pub fn new() -> Self {
 		let command_queues = CommandQueues {
 			model_commands: Vec::new(),
 			view_commands: Vec::new(),
+			events: Vec::new(),
 		};
 
 		let task_list = TaskList {
@@ -43,46 +52,68 @@ impl Executor {
 			wake_queue: Arc::new(Mutex::new(Vec::new())),
 
 			command_queues: Rc::new(RefCell::new(command_queues)),
-			hack_gamestate
+			clock: Rc::new(RefCell::new(FrameClock::new())),
 		}
 	}
 
-	pub fn queue<F>(&self, f: F) -> TaskId
-		where F: Future<Output=()> + 'static
+	pub fn queue<F>(&self, f: F) -> JoinHandle<F::Output>
+		where F: Future + 'static
 	{
+		let join_state = Rc::new(RefCell::new(JoinState {
+			result: None,
+			finished: false,
+			waker: None,
+		}));
+
+		let completer = JoinCompleter { state: Rc::clone(&join_state) };
+		let future = async move {
+			completer.complete(f.await);
+		};
+
 		let mut task_list = self.task_list.lock().unwrap();
 
 		task_list.task_id += 1;
 		let task_id = TaskId(task_list.task_id);
 
 		task_list.tasks.push(ExecutorTask{
-			future: Box::pin(f),
+			future: Box::pin(future),
 			task_id,
 		});
 
 		self.wake_queue.lock().unwrap().push(task_id);
-		task_id
-	}
 
-	pub fn is_task_running(&self, task_id: TaskId) -> bool {
-		self.task_list.lock().unwrap()
-			.tasks.iter()
-			.find(move |t| t.task_id == task_id)
-			.is_some()
+		JoinHandle {
+			task_id,
+			state: join_state,
+		}
 	}
 
-	pub fn num_queued_tasks(&self) -> usize {
-		self.task_list.lock().unwrap().tasks.len()
+	/// Drops a task without letting it finish. Anything awaiting its `JoinHandle` gets `None`.
+	/// Returns whether there was a task to cancel
+	pub fn cancel(&self, task_id: TaskId) -> bool {
+		let task = {
+			let mut task_list = self.task_list.lock().unwrap();
+			let position = task_list.tasks.iter().position(|t| t.task_id == task_id);
+			position.map(|p| task_list.tasks.swap_remove(p))
+		};
+
+		// Dropped outside of the lock, since dropping it wakes anything waiting on it
+		task.is_some()
 	}
 
 	pub fn resume_tasks(&self) {
 		let wake_queue = std::mem::replace(&mut *self.wake_queue.lock().unwrap(), Vec::new());
-		let mut task_list = self.task_list.lock().unwrap();
 
 		for task_id in wake_queue {
-			let task = task_list.tasks.iter_mut()
-				.find(|t| t.task_id == task_id)
-				.expect("Tried to wake nonexistant task id");
+			// Tasks are taken out of the list while they're polled, so that they're free to queue or cancel others.
+			// Tasks that have finished or been cancelled can still be woken by whatever held onto their waker
+			let mut task = {
+				let mut task_list = self.task_list.lock().unwrap();
+				match task_list.tasks.iter().position(|t| t.task_id == task_id) {
+					Some(position) => task_list.tasks.swap_remove(position),
+					None => continue,
+				}
+			};
 
 			let task_state = box TaskState {
 				wake_queue: self.wake_queue.clone(),
@@ -93,15 +124,29 @@ impl Executor {
 			let waker = unsafe { Waker::from_raw(raw_waker) };
 
 			match task.future.as_mut().poll(&mut task::Context::from_waker(&waker)) {
-				Poll::Ready(_) => {
-					task.task_id = INVALID_TASK_ID;
+				Poll::Ready(_) => {}
+				Poll::Pending => {
+					self.task_list.lock().unwrap().tasks.push(task);
 				}
-
-				Poll::Pending => {}
 			}
 		}
+	}
+
+	/// Moves the clock on by a frame, waking anything waiting for the next frame or for a sleep to run out
+	pub fn advance_clock(&self, dt: Duration) {
+		let wakers = self.clock.borrow_mut().advance(dt);
+		for waker in wakers {
+			waker.wake();
+		}
+	}
+
+	/// Resolves on the next frame to how long that frame took, in seconds
+	pub fn next_frame(&self) -> impl Future<Output=f32> {
+		NextFrame::new(Rc::clone(&self.clock))
+	}
 
-		task_list.tasks.retain(|t| t.task_id != INVALID_TASK_ID);
+	pub fn sleep(&self, duration: Duration) -> impl Future<Output=()> {
+		Sleep::new(Rc::clone(&self.clock), duration)
 	}
 
 	pub fn process_commands(&self, gamestate: &mut GameState, view: &mut impl View) {
@@ -109,17 +154,19 @@ impl Executor {
 			gamestate.submit_command(event, promise);
 		}
 
+		self.command_queues.borrow_mut().events.extend(gamestate.take_events());
+
 		for (event, promise) in self.command_queues.borrow_mut().view_commands.drain(..) {
 			view.submit_command(event, promise);
 		}
 	}
 
-	pub fn hack_game(&self) -> std::cell::Ref<GameState> { self.hack_gamestate.borrow() }
-	pub fn hack_game_mut(&self) -> std::cell::RefMut<GameState> { self.hack_gamestate.borrow_mut() }
-
+	pub(super) fn take_events(&self) -> Vec<GameEvent> {
+		std::mem::replace(&mut self.command_queues.borrow_mut().events, Vec::new())
+	}
 
 	pub(super) fn schedule_view_command<O>(&self, cmd: ViewCommand) -> impl Future<Output=O>
-		where O: Promisable, Promise<O>: Into<UntypedPromise>
+		where O: Promisable
 	{
 		CommandFuture {
 			command_queues: Rc::clone(&self.command_queues),
@@ -128,7 +175,7 @@ impl Executor {
 	}
 
 	pub(super) fn schedule_model_command<O>(&self, cmd: GameCommand) -> impl Future<Output=O>
-		where O: Promisable, Promise<O>: Into<UntypedPromise>
+		where O: Promisable
 	{
 		CommandFuture {
 			command_queues: Rc::clone(&self.command_queues),
@@ -141,7 +188,65 @@ impl Executor {
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct TaskId(usize);
 
-pub const INVALID_TASK_ID: TaskId = TaskId(0);
+
+/// Resolves to what a task returned once it finishes, or to `None` if it gets cancelled first
+pub struct JoinHandle<T> {
+	task_id: TaskId,
+	state: Rc<RefCell<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+	pub fn task_id(&self) -> TaskId { self.task_id }
+	pub fn is_finished(&self) -> bool { self.state.borrow().finished }
+}
+
+impl<T> Future for JoinHandle<T> {
+	type Output = Option<T>;
+
+	fn poll(self: Pin<&mut Self>, ctx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+		let mut state = self.state.borrow_mut();
+
+		if state.finished {
+			Poll::Ready(state.result.take())
+		} else {
+			state.waker = Some(ctx.waker().clone());
+			Poll::Pending
+		}
+	}
+}
+
+
+struct JoinState<T> {
+	result: Option<T>,
+	finished: bool,
+	waker: Option<Waker>,
+}
+
+/// Owned by a running task. Finishes its `JoinHandle` when the task completes, or when the task
+/// is dropped without completing
+struct JoinCompleter<T> {
+	state: Rc<RefCell<JoinState<T>>>,
+}
+
+impl<T> JoinCompleter<T> {
+	fn complete(self, value: T) {
+		self.state.borrow_mut().result = Some(value);
+	}
+}
+
+impl<T> Drop for JoinCompleter<T> {
+	fn drop(&mut self) {
+		let waker = {
+			let mut state = self.state.borrow_mut();
+			state.finished = true;
+			state.waker.take()
+		};
+
+		if let Some(waker) = waker {
+			waker.wake();
+		}
+	}
+}
 
 
 #[derive(Debug, Clone)]
@@ -206,7 +311,7 @@ struct CommandFuture<O: Promisable> {
 	state: CommandFutureState<O>,
 }
 
-impl<O: Promisable> Future for CommandFuture<O> where Promise<O>: Into<UntypedPromise> {
+impl<O: Promisable> Future for CommandFuture<O> {
 	type Output = O;
 
 	fn poll(mut self: Pin<&mut Self>, ctx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
@@ -219,7 +324,8 @@ impl<O: Promisable> Future for CommandFuture<O> where Promise<O>: Into<UntypedPr
 				}
 			}
 
-			CommandFutureState::ViewCommand(cmd) => {
+			CommandFutureState::ViewCommand(ref cmd) => {
+				let cmd = cmd.clone();
 				let promise = O::new_promise(ctx.waker().clone());
 				let future = promise.get_future();
 
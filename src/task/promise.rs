@@ -23,6 +23,11 @@ impl<T> Promise<T> {
 		}
 	}
 
+	/// Whether whatever was waiting on this promise has stopped waiting, e.g. because it lost a `select`
+	pub fn is_abandoned(&self) -> bool {
+		Rc::strong_count(&self.inner) == 1
+	}
+
 	pub fn fulfill(self, value: T) {
 		let mut inner = self.inner.borrow_mut();
 		assert!(inner.value.is_none(), "Promise has already been fulfilled");
@@ -55,64 +60,46 @@ struct SharedState<T> {
 
 
 use crate::task::PlayerCommand;
+use std::any::Any;
 
-pub enum UntypedPromise {
-	Void(Promise<()>),
-	Bool(Promise<bool>),
-	String(Promise<String>),
-	PlayerCommand(Promise<PlayerCommand>),
+/// A promise for any `Promisable` type, so that commands returning different types can share a queue.
+/// Whoever fulfills it needs to know what type it was made for
+pub struct UntypedPromise {
+	inner: Box<dyn Any>,
+	type_name: &'static str,
 }
 
 impl UntypedPromise {
-	pub fn void(self) -> Promise<()> {
-		match self {
-			UntypedPromise::Void(promise) => promise,
-			_ => panic!("Failed to unwrap untyped promise to ()")
-		}
-	}
-
-	pub fn bool(self) -> Promise<bool> {
-		match self {
-			UntypedPromise::Bool(promise) => promise,
-			_ => panic!("Failed to unwrap untyped promise to bool")
-		}
-	}
+	pub fn typed<T: Promisable>(self) -> Promise<T> {
+		let type_name = self.type_name;
 
-	pub fn string(self) -> Promise<String> {
-		match self {
-			UntypedPromise::String(promise) => promise,
-			_ => panic!("Failed to unwrap untyped promise to String")
+		match self.inner.downcast() {
+			Ok(promise) => *promise,
+			Err(_) => panic!("Failed to unwrap untyped promise to {}, it was made for {}", std::any::type_name::<T>(), type_name)
 		}
 	}
 
-	pub fn player_command(self) -> Promise<PlayerCommand> {
-		match self {
-			UntypedPromise::PlayerCommand(promise) => promise,
-			_ => panic!("Failed to unwrap untyped promise to PlayerCommand")
-		}
-	}
+	pub fn void(self) -> Promise<()> { self.typed() }
+	pub fn bool(self) -> Promise<bool> { self.typed() }
+	pub fn usize(self) -> Promise<usize> { self.typed() }
+	pub fn string(self) -> Promise<String> { self.typed() }
+	pub fn player_command(self) -> Promise<PlayerCommand> { self.typed() }
 }
 
 
-pub trait Promisable: Sized {
-	fn new_promise(waker: Waker) -> Promise<Self>;
+/// Anything that can be handed back through a promise
+pub trait Promisable: Sized + 'static {
+	fn new_promise(waker: Waker) -> Promise<Self> { Promise::new(waker) }
 }
 
-
-macro_rules! impl_promise_type {
-	($ty:ty, $id:ident) => {
-		impl Promisable for $ty {
-			fn new_promise(waker: Waker) -> Promise<Self> { Promise::new(waker) }
-		}
+impl<T: 'static> Promisable for T {}
 
 
-		impl From<Promise<$ty>> for UntypedPromise {
-			fn from(o: Promise<$ty>) -> UntypedPromise { UntypedPromise::$id(o) }
+impl<T: Promisable> From<Promise<T>> for UntypedPromise {
+	fn from(o: Promise<T>) -> UntypedPromise {
+		UntypedPromise {
+			inner: Box::new(o),
+			type_name: std::any::type_name::<T>(),
 		}
 	}
 }
-
-impl_promise_type!((), Void);
-impl_promise_type!(bool, Bool);
-impl_promise_type!(String, String);
-impl_promise_type!(PlayerCommand, PlayerCommand);
@@ -0,0 +1,56 @@
+use std::task::Poll;
+use std::pin::Pin;
+
+use crate::prelude::*;
+
+
+#[derive(Debug, Copy, Clone)]
+pub enum Either<L, R> {
+	Left(L),
+	Right(R),
+}
+
+
+/// Waits on two futures at once, resolving to whichever finishes first. The other is dropped.
+/// If both are ready at the same time, `a` wins
+pub fn select<A, B>(a: A, b: B) -> Select<A, B>
+	where A: Future, B: Future
+{
+	Select {
+		a: Box::pin(a),
+		b: Box::pin(b),
+	}
+}
+
+/// Like `select`, for when both futures resolve to the same type and it doesn't matter which won
+pub async fn race<T, A, B>(a: A, b: B) -> T
+	where A: Future<Output=T>, B: Future<Output=T>
+{
+	match select(a, b).await {
+		Either::Left(value) | Either::Right(value) => value,
+	}
+}
+
+
+pub struct Select<A, B> {
+	a: Pin<Box<A>>,
+	b: Pin<Box<B>>,
+}
+
+impl<A, B> Future for Select<A, B>
+	where A: Future, B: Future
+{
+	type Output = Either<A::Output, B::Output>;
+
+	fn poll(mut self: Pin<&mut Self>, ctx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+		if let Poll::Ready(value) = self.a.as_mut().poll(ctx) {
+			return Poll::Ready(Either::Left(value));
+		}
+
+		if let Poll::Ready(value) = self.b.as_mut().poll(ctx) {
+			return Poll::Ready(Either::Right(value));
+		}
+
+		Poll::Pending
+	}
+}
@@ -0,0 +1,37 @@
+use crate::prelude::*;
+use rand::distributions::{Standard, Distribution};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TrapKind {
+	/// Trips the player up, making them drop some of what they're carrying
+	Tripwire,
+	/// Fires a bolt at the player
+	Bolt,
+	/// A monster lying in wait
+	Ambush,
+	/// Whisks the player away to some other room
+	Portal,
+}
+
+
+impl TrapKind {
+	/// Whether the player gets the chance to disarm this kind of trap once it's been found
+	pub fn can_disarm(self) -> bool {
+		match self {
+			TrapKind::Tripwire | TrapKind::Bolt => true,
+			TrapKind::Ambush | TrapKind::Portal => false,
+		}
+	}
+}
+
+
+impl Distribution<TrapKind> for Standard {
+	fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> TrapKind {
+		[
+			TrapKind::Tripwire,
+			TrapKind::Bolt,
+			TrapKind::Ambush,
+			TrapKind::Portal,
+		].choose(rng).cloned().unwrap()
+	}
+}
@@ -2,7 +2,7 @@ use crate::prelude::*;
 use rand::distributions::{Standard, Distribution};
 use std::fmt;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Location(pub i32, pub i32);
 
 impl Location {
@@ -22,21 +22,23 @@ impl Location {
 		self.offset(ox, oy)
 	}
 
-	pub fn to_vec2i(self) -> Vec2i {
-		Vec2i::new(self.0, self.1)
-	}
-
 	/// Manhattan distance
 	pub fn distance(self, Location(ox, oy): Location) -> i32 {
 		let Location(sx, sy) = self;
 		(sx-ox).abs() + (sy-oy).abs()
 	}
+
+	/// Which way to go to get to `other`, if it's right next door
+	pub fn direction_to(self, other: Location) -> Option<Direction> {
+		Direction::iter_all()
+			.find(|&dir| self.offset_in_direction(dir) == other)
+	}
 }
 
 
 
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
 	North, East, South, West
 }
@@ -143,4 +145,50 @@ impl Bounds {
 		// +1 because Bounds is inclusive
 		((self.max.0 - self.min.0 + 1).max(0), (self.max.1 - self.min.1 + 1).max(0))
 	}
-}
\ No newline at end of file
+}
+
+
+
+/// A name written out as a unit enum variant. Enemy archetypes and equipment used to be enums,
+/// and are still saved the way they were back then
+#[derive(Debug)]
+pub struct VariantName(pub String);
+
+impl<'de> Deserialize<'de> for VariantName {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		use serde::de;
+
+		struct NameVisitor;
+
+		impl<'de> de::Visitor<'de> for NameVisitor {
+			type Value = VariantName;
+
+			fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+				f.write_str("a name")
+			}
+
+			fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+				use de::VariantAccess;
+
+				let (name, variant) = data.variant_seed(NameVisitor)?;
+				variant.unit_variant()?;
+				Ok(name)
+			}
+
+			fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+				Ok(VariantName(name.to_owned()))
+			}
+		}
+
+		// The variant itself is just an identifier
+		impl<'de> de::DeserializeSeed<'de> for NameVisitor {
+			type Value = VariantName;
+
+			fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+				deserializer.deserialize_identifier(self)
+			}
+		}
+
+		deserializer.deserialize_enum("VariantName", &[], NameVisitor)
+	}
+}
@@ -1,18 +1,29 @@
 pub mod text_view;
 pub mod gfx_view;
+pub mod scripted_view;
 
 pub use text_view::TextView;
 pub use gfx_view::GfxView;
+pub use scripted_view::ScriptedView;
 
-use crate::gamestate::{GameState, GameCommand};
-use crate::task::{UntypedPromise, ControllerMode};
+use crate::prelude::*;
+use crate::gamestate::{GameState, GameSummary};
+use crate::event::GameEvent;
+use crate::task::{UntypedPromise, ControllerMode, PlayerCommand};
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub enum ViewCommand {
 	GetPlayerCommand,
 	ShowMap { whole_map: bool },
+
+	/// The rooms the player is about to walk through, including where they are now and where they're going
+	ShowRoute { route: Vec<Location> },
 	ShowInventory,
-	GameCommand(GameCommand),
+
+	/// `rank` is where the game placed on the high score table for its seed, if it made it at all
+	ShowGameOver { summary: GameSummary, score: u32, rank: Option<usize> },
+
+	GameEvent(GameEvent),
 	PushControllerMode(ControllerMode),
 	PopControllerMode,
 }
@@ -23,4 +34,7 @@ pub trait View {
 	fn init(&mut self, gamestate: &GameState) {}
 	fn update(&mut self, gamestate: &GameState);
 	fn should_quit(&self) -> bool;
+
+	/// Called instead of submitting `GetPlayerCommand` when player commands are coming from a replay
+	fn on_replayed_player_command(&mut self, _: &PlayerCommand) {}
 }
@@ -7,16 +7,22 @@ mod animation;
 mod map_view;
 mod battle_view;
 mod merchant_view;
+mod disarm_view;
+mod equip_view;
+mod game_over_view;
 
 mod player_view;
 mod hud_view;
 
 use std::thread;
 use std::sync::mpsc::{channel, Sender, Receiver};
+use std::time::Instant;
 
 use crate::prelude::*;
-use crate::gamestate::{GameState, GameCommand, Inventory};
-use crate::task::{PlayerCommand, UntypedPromise, Promise, ControllerMode};
+use crate::gamestate::{GameState, Inventory};
+use crate::event::GameEvent;
+use crate::task::{PlayerCommand, UntypedPromise, Promise, ControllerMode, Executor};
+use crate::item::ItemCatalog;
 use super::{View, ViewCommand};
 
 use util::*;
@@ -25,6 +31,9 @@ use map_view::MapView;
 use player_view::PlayerView;
 use battle_view::BattleView;
 use merchant_view::MerchantView;
+use disarm_view::DisarmView;
+use equip_view::EquipView;
+use game_over_view::GameOverView;
 use hud_view::HudView;
 
 use gfx::Gfx;
@@ -42,6 +51,10 @@ pub struct GfxView {
 	window: window::Window,
 	gfx: Gfx,
 
+	/// Runs the camera and player animations, separately from the controllers
+	animation_executor: Executor,
+	last_frame: Instant,
+
 	should_quit: bool,
 	mouse_pos: Vec2,
 
@@ -49,14 +62,18 @@ pub struct GfxView {
 	player_view: PlayerView,
 	battle_view: BattleView,
 	merchant_view: MerchantView,
+	disarm_view: DisarmView,
+	equip_view: EquipView,
+	game_over_view: GameOverView,
 	hud_view: HudView,
 }
 
 
 impl GfxView {
-	pub fn new() -> GfxView {
+	pub fn new(catalog: &'static ItemCatalog) -> GfxView {
 		let window = window::Window::new().expect("Failed to create window");
-		let mut gfx = Gfx::new();
+		let animation_executor = Executor::new();
+		let mut gfx = Gfx::new(&animation_executor);
 
 		unsafe {
 			gl::Enable(gl::BLEND);
@@ -71,13 +88,16 @@ impl GfxView {
 		gfx.core.use_shader(shader);
 
 		let map_view = MapView::new(&mut gfx);
-		let player_view = PlayerView::new(&mut gfx);
+		let player_view = PlayerView::new(&mut gfx, &animation_executor);
 		let battle_view = BattleView::new();
 		let merchant_view = MerchantView::new();
+		let disarm_view = DisarmView::new();
+		let equip_view = EquipView::new();
+		let game_over_view = GameOverView::new();
 		let hud_view = HudView::new();
 
 		let (debug_command_tx, debug_command_rx) = channel();
-		thread::spawn(move || debug_command_thread(debug_command_tx));
+		thread::spawn(move || debug_command_thread(debug_command_tx, catalog));
 
 		GfxView {
 			commands: Vec::new(),
@@ -91,6 +111,9 @@ impl GfxView {
 			window,
 			gfx,
 
+			animation_executor,
+			last_frame: Instant::now(),
+
 			should_quit: false,
 			mouse_pos: Vec2::zero(),
 
@@ -98,6 +121,9 @@ impl GfxView {
 			player_view,
 			battle_view,
 			merchant_view,
+			disarm_view,
+			equip_view,
+			game_over_view,
 			hud_view,
 		}
 	}
@@ -150,10 +176,9 @@ impl GfxView {
 	}
 
 	fn push_player_command(&mut self, cmd: PlayerCommand) {
-		if let Some(promise) = self.player_command_promise.take() {
-			promise.fulfill(cmd);
-		} else {
-			self.player_commands.push(cmd);
+		match self.player_command_promise.take() {
+			Some(promise) if !promise.is_abandoned() => promise.fulfill(cmd),
+			_ => self.player_commands.push(cmd),
 		}
 	}
 
@@ -166,7 +191,7 @@ impl GfxView {
 
 				if self.player_commands.is_empty() {
 					let prev = self.player_command_promise.replace(promise);
-					assert!(prev.is_none(), "Trying to queue two GetPlayerCommands");
+					assert!(prev.map_or(true, |p| p.is_abandoned()), "Trying to queue two GetPlayerCommands");
 
 				} else {
 					promise.fulfill(self.player_commands.remove(0));
@@ -184,68 +209,38 @@ impl GfxView {
 				promise.void().fulfill(());
 			}
 
-			ViewCommand::ShowInventory => {
+			ViewCommand::ShowRoute { route } => {
+				super::text_view::print_route(gamestate, &route);
+				self.map_view.show_route(route);
 				promise.void().fulfill(());
 			}
 
-			ViewCommand::GameCommand(event) => {
-				use crate::gamestate::HealthModifyReason;
-				use crate::item::Item;
-				use std::cmp::Ordering;
-
-				match event {
-					GameCommand::GivePlayerItem(item, _) => match item {
-						Item::Food => println!("You found food!"),
-						Item::Treasure => println!("You found treasure!"),
-						Item::Key => println!("You found a key!"),
-						Item::Map => {
-							// TODO: println!("You found another map. It may have some value");
-							// how do I find out if player already had a map?
-							println!("You found a map!");
-						}
-
-						Item::Potion => println!("You found a potion"),
-
-						Item::Equipment(e) => {
-							// TODO: a/an obvs doesn't work
-							println!("You found a {:?}", e);
-						}
-					}
-
-					GameCommand::ModifyPlayerHealth(n, reason) => match n.cmp(&0) {
-						Ordering::Greater => {
-							println!("You gained {} health", n);
-						}
+			ViewCommand::ShowInventory => {
+				promise.void().fulfill(());
+			}
 
-						Ordering::Less => match reason {
-							HealthModifyReason::Attack => {
-								println!("You lost {} health!", -n);
-								if gamestate.player.is_dead() {
-									println!("Unfortunately, the strike is fatal");
-								}
-							}
+			ViewCommand::ShowGameOver { summary, score, rank } => {
+				super::text_view::print_game_summary(&summary, score, rank);
+				self.game_over_view.show_summary(summary, score, rank);
+				promise.void().fulfill(());
+			}
 
-							_ => {}
-						}
+			ViewCommand::GameEvent(event) => {
+				super::text_view::print_event(&event);
 
-						Ordering::Equal => {}
+				match event {
+					GameEvent::RoomEntered { .. } | GameEvent::GameLoaded => {
+						self.map_view.on_player_move(gamestate);
+						self.on_player_relocated(gamestate, promise.void());
 					}
 
-					GameCommand::MovePlayer(dir) => {
-						println!("You move {}", dir);
-						let world_loc = location_to_world(gamestate.player.location);
-
-						self.gfx.camera.start_move_to(world_loc.to_x0z());
-
-						self.map_view.on_player_move(gamestate);
-						self.player_view.on_player_move(gamestate.player.location, promise.void());
-						return;
+					GameEvent::PlayerTeleported { .. } | GameEvent::GameRestarted { .. } => {
+						self.map_view.on_player_teleport(gamestate);
+						self.on_player_relocated(gamestate, promise.void());
 					}
 
-					_ => {}
+					_ => promise.void().fulfill(()),
 				}
-
-				promise.void().fulfill(());
 			}
 
 			ViewCommand::PushControllerMode(mode) => {
@@ -255,6 +250,10 @@ impl GfxView {
 				self.map_view.on_mode_change(mode);
 				self.battle_view.on_mode_change(mode);
 				self.merchant_view.on_mode_change(mode);
+				self.disarm_view.on_mode_change(mode);
+				self.equip_view.on_mode_change(mode);
+				self.game_over_view.on_mode_change(mode);
+				self.hud_view.on_mode_change(mode);
 
 				promise.void().fulfill(());
 			}
@@ -267,6 +266,10 @@ impl GfxView {
 				self.map_view.on_mode_change(current_ctl);
 				self.battle_view.on_mode_change(current_ctl);
 				self.merchant_view.on_mode_change(current_ctl);
+				self.disarm_view.on_mode_change(current_ctl);
+				self.equip_view.on_mode_change(current_ctl);
+				self.game_over_view.on_mode_change(current_ctl);
+				self.hud_view.on_mode_change(current_ctl);
 
 				promise.void().fulfill(());
 			}
@@ -274,6 +277,15 @@ impl GfxView {
 	}
 }
 
+impl GfxView {
+	fn on_player_relocated(&mut self, gamestate: &GameState, promise: Promise<()>) {
+		let world_loc = location_to_world(gamestate.player.location);
+
+		self.gfx.camera.start_move_to(world_loc.to_x0z());
+		self.player_view.on_player_move(gamestate.player.location, promise);
+	}
+}
+
 impl View for GfxView {
 	fn submit_command(&mut self, cmd: ViewCommand, promise: UntypedPromise) {
 		self.commands.push((cmd, promise));
@@ -286,6 +298,13 @@ impl View for GfxView {
 	fn update(&mut self, gamestate: &GameState) {
 		self.process_events();
 
+		let now = Instant::now();
+		let dt = now - self.last_frame;
+		self.last_frame = now;
+
+		self.animation_executor.advance_clock(dt);
+		self.animation_executor.resume_tasks();
+
 		let commands = std::mem::replace(&mut self.commands, Vec::new());
 		for (cmd, promise) in commands {
 			self.run_command(gamestate, cmd, promise);
@@ -297,7 +316,7 @@ impl View for GfxView {
 		self.gfx.core.set_viewport(window_size);
 		self.gfx.core.set_bg_color(Color::grey(0.1));
 		self.gfx.core.clear();
-		self.gfx.camera.update(aspect);
+		self.gfx.camera.update(aspect, dt.as_secs_f32());
 
 		let ui_proj_view = Mat4::ortho_aspect(1.0, aspect, -100.0, 200.0);
 		let near_plane_pos = self.gfx.camera.inverse_projection_view() * self.mouse_pos.extend(0.0).extend(1.0);
@@ -310,8 +329,11 @@ impl View for GfxView {
 		self.map_view.update(&mut self.gfx, gamestate);
 		self.battle_view.update(&mut self.gfx, gamestate);
 		self.merchant_view.update(&mut self.gfx, gamestate);
+		self.disarm_view.update(&mut self.gfx, gamestate);
+		self.equip_view.update(&mut self.gfx, gamestate);
 		self.player_view.update(&mut self.gfx, gamestate);
 		self.hud_view.update(&mut self.gfx, gamestate);
+		self.game_over_view.update(&mut self.gfx);
 
 		self.gfx.draw_world_ui();
 
@@ -322,12 +344,17 @@ impl View for GfxView {
 	}
 
 	fn should_quit(&self) -> bool { self.should_quit }
+
+	fn on_replayed_player_command(&mut self, command: &PlayerCommand) {
+		println!("> {:?}", command);
+		self.map_view.on_awaiting_player_command();
+	}
 }
 
 
 fn print_map(state: &GameState) {
 	println!("==== map ====");
-	println!("{}", super::text_view::util::render_map(&state, state.map.bounds()));
+	println!("{}", super::text_view::util::render_map(&state, state.map.bounds(), &[]));
 	println!("=============");
 }
 
@@ -340,7 +367,7 @@ fn window_to_screen(window_size: Vec2i, pos: Vec2) -> Vec2 {
 
 
 
-fn debug_command_thread(tx: Sender<PlayerCommand>) {
+fn debug_command_thread(tx: Sender<PlayerCommand>, catalog: &'static ItemCatalog) {
 	use std::io::{Write, BufRead};
 
 	loop {
@@ -368,8 +395,26 @@ fn debug_command_thread(tx: Sender<PlayerCommand>) {
 
 			tx.send(PlayerCommand::Debug(parts))
 				.expect("Failed to send debug command");
+
+		} else if let Some(cmd) = parse_console_command(catalog, &command_str) {
+			tx.send(PlayerCommand::Main(cmd))
+				.expect("Failed to send console command");
+
 		} else {
 			println!("what does '{}' mean??", command_str);
 		}
 	}
-}
\ No newline at end of file
+}
+
+/// Commands that have no button, and so can only be issued through the console
+fn parse_console_command(catalog: &'static ItemCatalog, cmd: &str) -> Option<crate::controller::main::PlayerCommand> {
+	use crate::controller::main::PlayerCommand::*;
+
+	match cmd {
+		"save" => Some(Save),
+		"load" => Some(Load),
+		"restart" => Some(Restart),
+		_ => super::text_view::parse_goto_command(cmd)
+			.or_else(|| super::text_view::parse_equipment_command(catalog, cmd))
+	}
+}
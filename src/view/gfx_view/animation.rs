@@ -1,12 +1,13 @@
 use crate::prelude::*;
 use crate::task::promise::*;
-use crate::task::executor::TaskId;
+use crate::task::executor::{Executor, JoinHandle, TaskId};
 use std::task::Poll;
 use std::future::Future;
 use std::pin::Pin;
 
 pub struct AnimationQueue<V> {
-	inner: Rc<RefCell<AnimationQueueInner<V>>>
+	inner: Rc<RefCell<AnimationQueueInner<V>>>,
+	executor: Executor,
 }
 
 struct AnimationQueueInner<V> {
@@ -15,13 +16,14 @@ struct AnimationQueueInner<V> {
 
 
 impl<V> AnimationQueue<V> {
-	pub fn new() -> Self {
+	pub fn new(executor: &Executor) -> Self {
 		let inner = AnimationQueueInner {
 			tasks: Vec::new()
 		};
 
 		AnimationQueue {
-			inner: Rc::new(RefCell::new(inner))
+			inner: Rc::new(RefCell::new(inner)),
+			executor: executor.clone(),
 		}
 	}
 
@@ -29,12 +31,17 @@ impl<V> AnimationQueue<V> {
 		std::mem::replace(&mut self.inner.borrow_mut().tasks, Vec::new())
 	}
 
-	pub fn start<C, F>(&mut self, construct_future: C) -> TaskId
+	pub fn start<C, F>(&mut self, construct_future: C) -> JoinHandle<()>
 		where C: (FnOnce(AnimationContext<V>) -> F) + 'static,
 			F: Future<Output=()> + 'static
 	{
 		let ctx = AnimationContext::new(&self);
-		get_executor().queue(construct_future(ctx))
+		self.executor.queue(construct_future(ctx))
+	}
+
+	/// Stops an animation where it is. Anything it was going to do but hasn't yet is dropped
+	pub fn cancel(&mut self, task_id: TaskId) {
+		self.executor.cancel(task_id);
 	}
 }
 
@@ -43,11 +50,15 @@ impl<V> AnimationQueue<V> {
 
 pub struct AnimationContext<V> {
 	inner: Rc<RefCell<AnimationQueueInner<V>>>,
+	executor: Executor,
 }
 
 impl<V> AnimationContext<V> {
 	pub fn new(view: &AnimationQueue<V>) -> Self {
-		Self { inner: Rc::clone(&view.inner) }
+		Self {
+			inner: Rc::clone(&view.inner),
+			executor: view.executor.clone(),
+		}
 	}
 
 	pub fn run<F>(&self, f: F) -> impl Future<Output=()>
@@ -58,6 +69,30 @@ impl<V> AnimationContext<V> {
 			state: AnimationFutureState::Pending(Some(Box::new(f)))
 		}
 	}
+
+	/// Runs `apply` once a frame for `duration` seconds, with `ease` of how far through it is, from 0 to 1.
+	/// The last frame always gets `ease(1.0)`, however long the frames have taken
+	pub async fn tween<T, E, A>(&self, duration: f32, ease: E, apply: A)
+		where V: 'static, T: 'static, E: Fn(f32) -> T, A: Fn(&mut V, T) + Copy + 'static
+	{
+		let mut elapsed = 0.0;
+
+		loop {
+			let phase = (elapsed / duration).min(1.0);
+
+			// Asked for before `run` so that waiting on the view doesn't hold things up by an extra frame
+			let next_frame = self.executor.next_frame();
+
+			let value = ease(phase);
+			self.run(move |v| apply(v, value)).await;
+
+			if phase >= 1.0 {
+				break
+			}
+
+			elapsed += next_frame.await;
+		}
+	}
 }
 
 
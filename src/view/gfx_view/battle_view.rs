@@ -4,6 +4,7 @@ use super::gfx::{Gfx, ui};
 
 use crate::task::ControllerMode;
 use crate::gamestate::GameState;
+use crate::item::Item;
 use crate::controller::battle::PlayerCommand::*;
 
 
@@ -13,6 +14,7 @@ pub struct BattleView {
 	flee_button: ui::Hoverable,
 	attack_button: ui::Hoverable,
 	heal_button: ui::Hoverable,
+	potion_button: ui::Hoverable,
 }
 
 
@@ -24,6 +26,7 @@ impl BattleView {
 			flee_button: Default::default(),
 			attack_button: Default::default(),
 			heal_button: Default::default(),
+			potion_button: Default::default(),
 		}
 	}
 
@@ -47,6 +50,7 @@ impl BattleView {
 		let flee_button_pos = room_pos + Vec3::new(0.7, 0.01, -0.5);
 		let attack_button_pos = room_pos + Vec3::new(0.7, 0.01, -0.2);
 		let heal_button_pos = room_pos + Vec3::new(0.7, 0.01, 0.1);
+		let potion_button_pos = room_pos + Vec3::new(0.7, 0.01, 0.4);
 
 		// Flee button
 		let region = ui::Region::new_ground(flee_button_pos, size);
@@ -70,5 +74,17 @@ impl BattleView {
 
 		let color = heal_palette.color(self.heal_button.state());
 		gfx.ui.quad(region, color);
+
+
+		// Potion button
+		if gamestate.player.inventory.has(Item::Potion) {
+			let region = ui::Region::new_ground(potion_button_pos, size);
+			gfx.ui.update_interact_region(&mut self.potion_button, &region, || UsePotion);
+
+			let color = ui::palette().potion.color(self.potion_button.state());
+			gfx.ui.quad(region, color);
+		} else {
+			self.potion_button.reset();
+		}
 	}
 }
\ No newline at end of file
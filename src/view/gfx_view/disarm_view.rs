@@ -0,0 +1,76 @@
+use crate::prelude::*;
+use super::util::*;
+use super::gfx::{Gfx, ui};
+
+use crate::task::ControllerMode;
+use crate::gamestate::GameState;
+use crate::controller::disarm::PlayerCommand::*;
+
+
+pub struct DisarmView {
+	active: bool,
+
+	leave_button: ui::Hoverable,
+	attempt_button: ui::Hoverable,
+	inspect_button: ui::Hoverable,
+}
+
+
+impl DisarmView {
+	pub fn new() -> Self {
+		DisarmView {
+			active: false,
+
+			leave_button: Default::default(),
+			attempt_button: Default::default(),
+			inspect_button: Default::default(),
+		}
+	}
+
+	pub fn on_mode_change(&mut self, mode: ControllerMode) {
+		self.active = matches!(mode, ControllerMode::Disarm);
+		if !self.active {
+			self.leave_button.reset();
+			self.attempt_button.reset();
+			self.inspect_button.reset();
+		}
+	}
+
+	pub fn update(&mut self, gfx: &mut Gfx, gamestate: &GameState) {
+		if !self.active { return }
+
+		let size = Vec2::splat(0.2);
+		let room_pos = location_to_world(gamestate.player.location).to_x0z();
+
+		let leave_palette = ui::HoverablePalette::new(Color::rgb(0.5, 0.0, 1.0));
+		let attempt_palette = ui::HoverablePalette::new(Color::rgb(1.0, 0.6, 0.0));
+		let inspect_palette = ui::HoverablePalette::new(Color::rgb(0.3, 0.5, 1.0));
+
+		let leave_button_pos = room_pos + Vec3::new(0.7, 0.01, -0.5);
+		let attempt_button_pos = room_pos + Vec3::new(0.7, 0.01, -0.2);
+		let inspect_button_pos = room_pos + Vec3::new(0.7, 0.01, 0.1);
+
+		// Leave button
+		let region = ui::Region::new_ground(leave_button_pos, size);
+		gfx.ui.update_interact_region(&mut self.leave_button, &region, || Leave);
+
+		let color = leave_palette.color(self.leave_button.state());
+		gfx.ui.arrow(region, Direction::East, color);
+
+
+		// Attempt button
+		let region = ui::Region::new_ground(attempt_button_pos, size);
+		gfx.ui.update_interact_region(&mut self.attempt_button, &region, || Attempt);
+
+		let color = attempt_palette.color(self.attempt_button.state());
+		gfx.ui.quad(region, color);
+
+
+		// Inspect button
+		let region = ui::Region::new_ground(inspect_button_pos, size);
+		gfx.ui.update_interact_region(&mut self.inspect_button, &region, || Inspect);
+
+		let color = inspect_palette.color(self.inspect_button.state());
+		gfx.ui.quad(region, color);
+	}
+}
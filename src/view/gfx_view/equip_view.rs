@@ -0,0 +1,63 @@
+use crate::prelude::*;
+use super::util::*;
+use super::gfx::{Gfx, ui};
+
+use crate::task::ControllerMode;
+use crate::gamestate::GameState;
+use crate::controller::equip::PlayerCommand::*;
+
+
+pub struct EquipView {
+	active: bool,
+
+	keep_button: ui::Hoverable,
+	swap_button: ui::Hoverable,
+}
+
+
+impl EquipView {
+	pub fn new() -> Self {
+		EquipView {
+			active: false,
+
+			keep_button: Default::default(),
+			swap_button: Default::default(),
+		}
+	}
+
+	pub fn on_mode_change(&mut self, mode: ControllerMode) {
+		self.active = matches!(mode, ControllerMode::Equip);
+		if !self.active {
+			self.keep_button.reset();
+			self.swap_button.reset();
+		}
+	}
+
+	pub fn update(&mut self, gfx: &mut Gfx, gamestate: &GameState) {
+		if !self.active { return }
+
+		let size = Vec2::splat(0.2);
+		let room_pos = location_to_world(gamestate.player.location).to_x0z();
+
+		let keep_palette = ui::HoverablePalette::new(Color::rgb(0.5, 0.0, 1.0));
+		let swap_palette = ui::HoverablePalette::new(Color::rgb(0.8, 0.8, 0.8));
+
+		let keep_button_pos = room_pos + Vec3::new(0.7, 0.01, -0.5);
+		let swap_button_pos = room_pos + Vec3::new(0.7, 0.01, -0.2);
+
+		// Keep button
+		let region = ui::Region::new_ground(keep_button_pos, size);
+		gfx.ui.update_interact_region(&mut self.keep_button, &region, || Keep);
+
+		let color = keep_palette.color(self.keep_button.state());
+		gfx.ui.arrow(region, Direction::East, color);
+
+
+		// Swap button
+		let region = ui::Region::new_ground(swap_button_pos, size);
+		gfx.ui.update_interact_region(&mut self.swap_button, &region, || Swap);
+
+		let color = swap_palette.color(self.swap_button.state());
+		gfx.ui.quad(region, color);
+	}
+}
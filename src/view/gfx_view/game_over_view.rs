@@ -0,0 +1,114 @@
+use crate::prelude::*;
+use super::gfx::{Gfx, ui};
+
+use crate::task::ControllerMode;
+use crate::gamestate::{GameSummary, GameOutcome};
+use crate::controller::game_over::PlayerCommand::*;
+
+
+/// Each blip in the score tally is worth this many points
+const POINTS_PER_BLIP: u32 = 25;
+
+
+pub struct GameOverView {
+	summary: Option<GameSummary>,
+	score: u32,
+	rank: Option<usize>,
+
+	restart_button: ui::Hoverable,
+	quit_button: ui::Hoverable,
+}
+
+
+impl GameOverView {
+	pub fn new() -> Self {
+		GameOverView {
+			summary: None,
+			score: 0,
+			rank: None,
+
+			restart_button: Default::default(),
+			quit_button: Default::default(),
+		}
+	}
+
+	pub fn on_mode_change(&mut self, mode: ControllerMode) {
+		if !matches!(mode, ControllerMode::GameOver) {
+			self.summary = None;
+			self.restart_button.reset();
+			self.quit_button.reset();
+		}
+	}
+
+	pub fn show_summary(&mut self, summary: GameSummary, score: u32, rank: Option<usize>) {
+		self.summary = Some(summary);
+		self.score = score;
+		self.rank = rank;
+	}
+
+	pub fn update(&mut self, gfx: &mut Gfx) {
+		let summary = match self.summary {
+			Some(summary) => summary,
+			None => return,
+		};
+
+		let banner_color = match summary.outcome {
+			GameOutcome::Victory => ui::palette().treasure.base,
+			GameOutcome::Defeat => ui::palette().health.base,
+		};
+
+		let banner_region = ui::Region::new(Vec3::new(0.0, 0.4, 0.0), Vec2::new(1.0, 0.12), ui::Context::ScreenCenter);
+		gfx.ui.quad(banner_region, banner_color);
+
+		// A new high score gets a pair of gold markers either side of the banner
+		if self.rank == Some(1) {
+			for &pos_x in &[-0.56, 0.56] {
+				let marker_region = ui::Region::new(Vec3::new(pos_x, 0.4, 0.0), Vec2::splat(0.06), ui::Context::ScreenCenter);
+				gfx.ui.quad(marker_region, ui::palette().treasure.base);
+			}
+		}
+
+		build_tally(gfx, summary.rooms_explored, 0.2, Color::grey(0.4));
+		build_tally(gfx, summary.enemies_killed, 0.1, Color::rgb(1.0, 0.0, 0.0));
+		build_tally(gfx, summary.treasure, 0.0, ui::palette().treasure.base);
+		build_tally(gfx, (self.score / POINTS_PER_BLIP) as usize, -0.1, Color::white());
+
+		let size = Vec2::splat(0.2);
+
+		let restart_palette = ui::HoverablePalette::new(Color::rgb(0.0, 0.8, 0.3));
+		let quit_palette = ui::HoverablePalette::new(Color::rgb(0.5, 0.0, 1.0));
+
+		// Restart button
+		let region = ui::Region::new(Vec3::new(-0.15, -0.3, 0.0), size, ui::Context::ScreenCenter);
+		gfx.ui.update_interact_region(&mut self.restart_button, &region, || Restart);
+
+		let color = restart_palette.color(self.restart_button.state());
+		gfx.ui.quad(region, color);
+
+
+		// Quit button
+		let region = ui::Region::new(Vec3::new(0.15, -0.3, 0.0), size, ui::Context::ScreenCenter);
+		gfx.ui.update_interact_region(&mut self.quit_button, &region, || Quit);
+
+		let color = quit_palette.color(self.quit_button.state());
+		gfx.ui.arrow(region, Direction::East, color);
+	}
+}
+
+
+/// A centered row of blips, one for each of `count`
+fn build_tally(gfx: &mut Gfx, count: usize, pos_y: f32, color: Color) {
+	let margin = 0.01;
+	let width = 0.02;
+	let height = 0.06;
+
+	let blip_size = Vec2::new(width, height);
+	let row_width = count as f32 * (width + margin) - margin;
+
+	for i in 0..count {
+		let pos_x = i as f32 * (width + margin) + width/2.0 - row_width/2.0;
+		let pos = Vec3::new(pos_x, pos_y, 0.0);
+
+		gfx.ui.quad((pos, blip_size, ui::Context::ScreenCenter), color);
+	}
+}
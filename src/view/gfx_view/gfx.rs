@@ -8,6 +8,7 @@ pub mod camera;
 
 // use crate::prelude::*;
 use mesh_builder::MeshBuilder;
+use crate::task::Executor;
 
 pub struct Gfx {
 	pub core: core::Core,
@@ -19,7 +20,7 @@ pub struct Gfx {
 
 
 impl Gfx {
-	pub fn new() -> Gfx {
+	pub fn new(animation_executor: &Executor) -> Gfx {
 		let mut core = core::Core::new();
 
 		let ui_mesh_id = core.new_mesh();
@@ -31,7 +32,7 @@ impl Gfx {
 
 		Gfx {
 			core,
-			camera: camera::Camera::new(),
+			camera: camera::Camera::new(animation_executor),
 
 			ui: ui::Ui::new(),
 			ui_builder,
@@ -1,5 +1,7 @@
 use crate::prelude::*;
 use crate::view::gfx_view::animation::AnimationQueue;
+use crate::task::Executor;
+use crate::task::executor::TaskId;
 
 /// An orbit camera
 pub struct Camera {
@@ -19,6 +21,11 @@ pub struct Camera {
 	// Animation stuff
 	animation_queue: AnimationQueue<Camera>,
 	timer: f32,
+
+	// Starting a new animation cuts short any other of the same kind
+	move_animation: Option<TaskId>,
+	zoom_animation: Option<TaskId>,
+	rotate_animation: Option<TaskId>,
 }
 
 
@@ -28,7 +35,7 @@ const DEFAULT_PITCH: f32 = -PI/6.0;
 
 
 impl Camera {
-	pub fn new() -> Self {
+	pub fn new(animation_executor: &Executor) -> Self {
 		Self {
 			view_size: DEFAULT_VIEW_SIZE,
 			view_dist: 3.0,
@@ -41,18 +48,22 @@ impl Camera {
 			inv_projection_view: Mat4::ident(),
 			orientation: Quat::ident(),
 
-			animation_queue: AnimationQueue::new(),
+			animation_queue: AnimationQueue::new(animation_executor),
 			timer: 0.0,
+
+			move_animation: None,
+			zoom_animation: None,
+			rotate_animation: None,
 		}
 	}
 
-	pub fn update(&mut self, aspect: f32) {
+	pub fn update(&mut self, aspect: f32, dt: f32) {
 		for (f, p) in self.animation_queue.get() {
 			f(self);
 			p.fulfill(());
 		}
 
-		self.timer += 1.0/60.0;
+		self.timer += dt;
 
 		let projection = Mat4::ortho_aspect(self.view_size, aspect, -100.0, 200.0);
 		self.orientation = Quat::new(Vec3::from_y(1.0), self.yaw + self.timer.sin() as f32*0.02)
@@ -82,63 +93,55 @@ impl Camera {
 	}
 
 	pub fn start_move_to(&mut self, target: Vec3) {
+		if let Some(task) = self.move_animation.take() {
+			self.animation_queue.cancel(task);
+		}
+
 		let start = self.position;
 		let end = target;
 
-		self.animation_queue.start(move |ctx| async move {
-			let mut phase = 0.0;
-
-			while phase < 1.0 {
-				phase += 0.9/60.0;
-
-				let new_pos = phase.ease_exp_out(start, end);
-				ctx.run(move |camera| camera.position = new_pos).await;
-			}
+		let animation = self.animation_queue.start(move |ctx| async move {
+			ctx.tween(1.1, move |phase| phase.ease_exp_out(start, end), |camera, pos| camera.position = pos).await;
 
+			// Exponential easing never quite gets there
 			ctx.run(move |camera| camera.position = end).await;
 		});
+
+		self.move_animation = Some(animation.task_id());
 	}
 
 	pub fn start_zoom_to(&mut self, target: f32) {
+		if let Some(task) = self.zoom_animation.take() {
+			self.animation_queue.cancel(task);
+		}
+
 		let start = self.view_size;
 		let end = target;
 
-		self.animation_queue.start(move |ctx| async move {
-			let mut phase = 0.0;
-
-			while phase < 1.0 {
-				phase += 2.0/60.0;
-
-				let size = phase.ease_quad_out(start, end);
-				ctx.run(move |camera| camera.view_size = size).await;
-			}
-
-			ctx.run(move |camera| camera.view_size = end).await;
+		let animation = self.animation_queue.start(move |ctx| async move {
+			ctx.tween(0.5, move |phase| phase.ease_quad_out(start, end), |camera, size| camera.view_size = size).await;
 		});
+
+		self.zoom_animation = Some(animation.task_id());
 	}
 
 	pub fn start_rotate_to(&mut self, yaw: f32, pitch: f32) {
+		if let Some(task) = self.rotate_animation.take() {
+			self.animation_queue.cancel(task);
+		}
+
 		let start = (self.yaw, self.pitch);
 		let end = (yaw, pitch);
 
-		self.animation_queue.start(move |ctx| async move {
-			let mut phase = 0.0;
-
-			while phase < 1.0 {
-				phase += 2.0/60.0;
+		let animation = self.animation_queue.start(move |ctx| async move {
+			let ease = move |phase: f32| (phase.ease_quad_out(start.0, end.0), phase.ease_quad_out(start.1, end.1));
 
-				let yaw = phase.ease_quad_out(start.0, end.0);
-				let pitch = phase.ease_quad_out(start.1, end.1);
-				ctx.run(move |camera| {
-					camera.yaw = yaw;
-					camera.pitch = pitch;
-				}).await;
-			}
-
-			ctx.run(move |camera| {
+			ctx.tween(0.5, ease, |camera, (yaw, pitch)| {
 				camera.yaw = yaw;
 				camera.pitch = pitch;
 			}).await;
 		});
+
+		self.rotate_animation = Some(animation.task_id());
 	}
 }
\ No newline at end of file
@@ -460,6 +460,7 @@ pub struct GlobalPalette {
 	pub hunger: HoverablePalette,
 	pub map: HoverablePalette,
 	pub treasure: HoverablePalette,
+	pub potion: HoverablePalette,
 	pub movement: HoverablePalette,
 }
 
@@ -474,6 +475,7 @@ pub fn palette() -> &'static GlobalPalette {
 				map: HoverablePalette::new(Color::rgb(0.5, 0.0, 1.0)),
 				food: HoverablePalette::new(Color::rgb(0.6, 0.5, 0.2)),
 				treasure: HoverablePalette::new(Color::rgb(0.3, 0.6, 0.2)),
+				potion: HoverablePalette::new(Color::rgb(0.8, 0.2, 0.6)),
 				movement: HoverablePalette::new(Color::rgba(1.0, 0.4, 0.5, 0.2))
 					.with_hover(Color::rgb(1.0, 0.4, 0.5)),
 			}
@@ -1,20 +1,31 @@
 use crate::prelude::*;
 use super::gfx::{Gfx, ui};
 
+use crate::task::ControllerMode;
 use crate::gamestate::GameState;
 use crate::item::Item;
 
 pub struct HudView {
+	in_main_mode: bool,
+
 	map_hoverable: ui::Hoverable,
+	potion_hoverable: ui::Hoverable,
 }
 
 impl HudView {
 	pub fn new() -> Self {
 		HudView {
+			in_main_mode: false,
+
 			map_hoverable: Default::default(),
+			potion_hoverable: Default::default(),
 		}
 	}
 
+	pub fn on_mode_change(&mut self, mode: ControllerMode) {
+		self.in_main_mode = matches!(mode, ControllerMode::Main);
+	}
+
 	pub fn update(&mut self, gfx: &mut Gfx, gamestate: &GameState) {
 		let margin = 0.01;
 		let width = 0.03;
@@ -81,5 +92,25 @@ impl HudView {
 		} else {
 			self.map_hoverable.reset();
 		}
+
+		// Battle mode has its own potion button
+		if self.in_main_mode && gamestate.player.inventory.has(Item::Potion) {
+			let size = Vec2::splat(0.2);
+			let pos = Vec3::new(0.11, -0.33, 0.0);
+
+			let region = ui::Region::new(pos, size, ui::Context::ScreenTopLeft);
+
+			gfx.ui.update_interact_region(
+				&mut self.potion_hoverable,
+				&region,
+				|| crate::controller::main::PlayerCommand::UsePotion
+			);
+
+			let color = ui::palette().potion.color(self.potion_hoverable.state());
+			gfx.ui.quad(region, color);
+
+		} else {
+			self.potion_hoverable.reset();
+		}
 	}
 }
\ No newline at end of file
@@ -16,6 +16,9 @@ pub struct MapView {
 	mode_change: Option<ControllerMode>,
 	full_map_promise: Option<Promise<()>>,
 
+	/// Where the player is being taken, shown until they're next asked what to do
+	route: Vec<Location>,
+
 	interact_hoverable: ui::Hoverable,
 	close_map_hoverable: ui::Hoverable,
 }
@@ -37,6 +40,8 @@ impl MapView {
 			mode_change: None,
 			full_map_promise: None,
 
+			route: Vec::new(),
+
 			interact_hoverable: Default::default(),
 			close_map_hoverable: Default::default(),
 		}
@@ -60,15 +65,24 @@ impl MapView {
 			}
 
 			Some(ControllerMode::Battle)
-			| Some(ControllerMode::Merchant) => {
+			| Some(ControllerMode::Merchant)
+			| Some(ControllerMode::Disarm)
+			| Some(ControllerMode::Equip) => {
 				gfx.camera.start_zoom_to(0.9);
 				gfx.camera.start_rotate_to(PI/5.0, -PI/9.0);
 			}
 
+			// Pull back to show everything that was explored
+			Some(ControllerMode::GameOver) => {
+				gfx.camera.start_zoom_to(10.0);
+				gfx.camera.start_rotate_to(PI/30.0, -PI/3.0);
+			}
+
 			None => {}
 		}
 
 		build_map(gfx, &gamestate.map);
+		build_route(gfx, &self.route);
 
 		if self.player_can_move() {
 			for view in self.door_views.iter_mut() {
@@ -130,7 +144,13 @@ impl MapView {
 	}
 
 	pub fn on_player_move(&mut self, gamestate: &GameState) {
-		assert!(self.player_can_move());
+		// Following a route moves the player room after room, without waiting on them in between
+		assert!(self.player_can_move() || !self.route.is_empty());
+		self.on_player_teleport(gamestate);
+	}
+
+	/// Like `on_player_move`, but may happen at any time - e.g., in the middle of an encounter
+	pub fn on_player_teleport(&mut self, gamestate: &GameState) {
 		self.player_move_in_progress = true;
 
 		let world_pos = location_to_world(gamestate.player.location);
@@ -142,6 +162,10 @@ impl MapView {
 		}
 	}
 
+	pub fn show_route(&mut self, route: Vec<Location>) {
+		self.route = route;
+	}
+
 	pub fn show_map(&mut self, promise: Promise<()>) {
 		assert!(self.full_map_promise.is_none());
 		self.full_map_promise = Some(promise);
@@ -153,6 +177,7 @@ impl MapView {
 	// finish, and for encounters to run
 	pub fn on_awaiting_player_command(&mut self) {
 		self.player_move_in_progress = false;
+		self.route.clear();
 	}
 }
 
@@ -178,6 +203,21 @@ fn build_map(gfx: &mut Gfx, map: &crate::map::Map) {
 	}
 }
 
+fn build_route(gfx: &mut Gfx, route: &[Location]) {
+	let color = Color::rgb(1.0, 0.4, 0.5);
+
+	for step in route.windows(2) {
+		let dir = match step[0].direction_to(step[1]) {
+			Some(dir) => direction_to_offset(dir),
+			None => continue,
+		};
+
+		let pos = location_to_world(step[0]) + dir * 0.5;
+		let size = dir + dir.perp() * 0.1;
+		gfx.ui.quad((pos.to_x0z() + Vec3::from_y(0.005), size, ui::Context::Ground), color);
+	}
+}
+
 fn direction_to_offset(d: Direction) -> Vec2 {
 	match d {
 		Direction::North => Vec2::from_y(-1.0),
@@ -5,7 +5,8 @@ use super::gfx::vertex::ColorVertex;
 use super::gfx::mesh_builder::MeshBuilder;
 
 use crate::gamestate::GameState;
-use crate::task::Promise;
+use crate::task::{Promise, Executor};
+use crate::task::executor::TaskId;
 
 use super::animation::{AnimationQueue, AnimationContext};
 
@@ -15,14 +16,21 @@ pub struct PlayerView {
 	mb: ColorMeshBuilder,
 	pos: Vec3,
 	animation_queue: AnimationQueue<PlayerView>,
+
+	move_animation: Option<TaskId>,
+	/// Fulfilled once the player gets where they're going, or when a new move cuts this one short
+	move_promise: Option<Promise<()>>,
 }
 
 impl PlayerView {
-	pub fn new(gfx: &mut gfx::Gfx) -> PlayerView {
+	pub fn new(gfx: &mut gfx::Gfx, animation_executor: &Executor) -> PlayerView {
 		PlayerView {
 			mb: ColorMeshBuilder::new(gfx.core.new_mesh()),
 			pos: Vec3::zero(),
-			animation_queue: AnimationQueue::new(),
+			animation_queue: AnimationQueue::new(animation_executor),
+
+			move_animation: None,
+			move_promise: None,
 		}
 	}
 
@@ -51,25 +59,35 @@ impl PlayerView {
 	}
 
 	pub fn on_player_move(&mut self, to: Location, promise: Promise<()>) {
-		let from = self.pos.to_xz();
-		let to = location_to_world(to);
+		if let Some(task) = self.move_animation.take() {
+			self.animation_queue.cancel(task);
+		}
 
-		self.animation_queue.start(move |ctx| async move {
-			let mut phase = 0.0;
+		if let Some(prev_promise) = self.move_promise.replace(promise) {
+			prev_promise.fulfill(());
+		}
 
-			while phase < 1.0 {
-				phase += 0.015;
+		let from = self.pos.to_xz();
+		let to = location_to_world(to);
 
+		let animation = self.animation_queue.start(move |ctx| async move {
+			let ease = move |phase: f32| {
 				let pos = phase.ease_quad_inout(from, to);
 				let hop = (phase * 4.0 * PI).sin().abs() * 0.1;
-				let new_pos = pos.to_x0z() + Vec3::from_y(hop);
+				pos.to_x0z() + Vec3::from_y(hop)
+			};
 
-				ctx.run(move |v| v.pos = new_pos).await;
-			}
+			ctx.tween(1.1, ease, |v, pos| v.pos = pos).await;
 
-			ctx.run(move |v| v.pos = to.to_x0z()).await;
+			ctx.run(move |v| {
+				v.pos = to.to_x0z();
 
-			promise.fulfill(());
+				if let Some(promise) = v.move_promise.take() {
+					promise.fulfill(());
+				}
+			}).await;
 		});
+
+		self.move_animation = Some(animation.task_id());
 	}
 }
@@ -0,0 +1,134 @@
+use crate::prelude::*;
+use crate::gamestate::GameState;
+use crate::task::{PlayerCommand, UntypedPromise, ControllerMode};
+use crate::item::ItemCatalog;
+use super::{View, ViewCommand};
+use super::text_view::parse_player_command;
+
+use std::path::Path;
+
+
+/// Runs without any input or output, pulling player commands from a script of lines as they would be typed
+/// into the `TextView`, and fulfilling everything else immediately. Everything that happens is recorded
+/// to a transcript. Quits once the script runs out.
+pub struct ScriptedView {
+	script: Box<dyn Iterator<Item=String>>,
+	controller_mode_stack: Vec<ControllerMode>,
+
+	/// For telling which items the script is talking about
+	catalog: &'static ItemCatalog,
+
+	transcript: Vec<String>,
+	should_quit: bool,
+}
+
+
+impl ScriptedView {
+	/// Lines are parsed lazily, since what they mean depends on the controller mode when they're reached
+	pub fn from_lines<I>(lines: I, catalog: &'static ItemCatalog) -> ScriptedView
+		where I: IntoIterator<Item=String>, I::IntoIter: 'static
+	{
+		ScriptedView {
+			script: Box::new(lines.into_iter()),
+			controller_mode_stack: Vec::new(),
+
+			catalog,
+
+			transcript: Vec::new(),
+			should_quit: false,
+		}
+	}
+
+	/// Blank lines and lines starting with `#` are skipped
+	pub fn from_file(path: impl AsRef<Path>, catalog: &'static ItemCatalog) -> Result<ScriptedView, Box<dyn Error>> {
+		let contents = std::fs::read_to_string(path)?;
+
+		let lines: Vec<String> = contents.lines()
+			.map(str::trim)
+			.filter(|l| !l.is_empty() && !l.starts_with('#'))
+			.map(str::to_owned)
+			.collect();
+
+		Ok(ScriptedView::from_lines(lines, catalog))
+	}
+
+	pub fn transcript(&self) -> &[String] { &self.transcript }
+
+	fn current_controller_mode(&self) -> ControllerMode {
+		self.controller_mode_stack.last()
+			.cloned()
+			.expect("Empty controller stack!")
+	}
+
+	fn next_player_command(&mut self) -> Option<PlayerCommand> {
+		let controller_mode = self.current_controller_mode();
+
+		for line in &mut self.script {
+			self.transcript.push(format!("> {}", line));
+
+			match parse_player_command(self.catalog, controller_mode, &line) {
+				Some(command) => return Some(command),
+				None => self.transcript.push(format!("what does '{}' mean??", line)),
+			}
+		}
+
+		None
+	}
+}
+
+impl View for ScriptedView {
+	fn submit_command(&mut self, cmd: ViewCommand, promise: UntypedPromise) {
+		match cmd {
+			ViewCommand::GetPlayerCommand => {
+				match self.next_player_command() {
+					Some(command) => promise.player_command().fulfill(command),
+					None => {
+						self.transcript.push("[script finished]".to_owned());
+						self.should_quit = true;
+					}
+				}
+			}
+
+			ViewCommand::ShowMap { whole_map } => {
+				self.transcript.push(format!("[show map, whole map: {}]", whole_map));
+				promise.void().fulfill(());
+			}
+
+			ViewCommand::ShowRoute { route } => {
+				self.transcript.push(format!("[show route {:?}]", route));
+				promise.void().fulfill(());
+			}
+
+			ViewCommand::ShowInventory => {
+				self.transcript.push("[show inventory]".to_owned());
+				promise.void().fulfill(());
+			}
+
+			ViewCommand::ShowGameOver { summary, score, rank } => {
+				self.transcript.push(format!("[game over: {:?}, score {}, rank {:?}]", summary, score, rank));
+				promise.void().fulfill(());
+			}
+
+			ViewCommand::GameEvent(event) => {
+				self.transcript.push(format!("{:?}", event));
+				promise.void().fulfill(());
+			}
+
+			ViewCommand::PushControllerMode(mode) => {
+				self.controller_mode_stack.push(mode);
+				self.transcript.push(format!("[mode transition -> {:?}]", self.controller_mode_stack));
+				promise.void().fulfill(());
+			}
+
+			ViewCommand::PopControllerMode => {
+				self.controller_mode_stack.pop();
+				self.transcript.push(format!("[mode transition {:?} <-]", self.controller_mode_stack));
+				promise.void().fulfill(());
+			}
+		}
+	}
+
+	fn update(&mut self, _: &GameState) {}
+
+	fn should_quit(&self) -> bool { self.should_quit }
+}
@@ -2,22 +2,43 @@ pub mod render_buffer;
 pub mod util;
 
 use crate::prelude::*;
-use crate::gamestate::{GameState, GameCommand, Inventory};
-use crate::task::{PlayerCommand, UntypedPromise, ControllerMode};
+use crate::gamestate::{GameState, GameSummary, Player};
+use crate::event::GameEvent;
+use crate::task::{PlayerCommand, UntypedPromise, Promise, ControllerMode};
+use crate::item::ItemCatalog;
 use super::{View, ViewCommand};
 
+use std::thread;
+use std::sync::mpsc::{channel, Sender, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
 
 pub struct TextView {
 	commands: Vec<(ViewCommand, UntypedPromise)>,
 	controller_mode_stack: Vec<ControllerMode>,
+	should_quit: bool,
+
+	/// Lines are read from stdin on their own thread, so that a realtime game carries on while the player
+	/// makes up their mind. The thread is only started once there's a player to read from
+	input_rx: Option<Receiver<String>>,
+	player_command_promise: Option<Promise<PlayerCommand>>,
+
+	/// For telling which items the player is talking about
+	catalog: &'static ItemCatalog,
 }
 
 
 impl TextView {
-	pub fn new() -> TextView {
+	pub fn new(catalog: &'static ItemCatalog) -> TextView {
 		TextView {
 			commands: Vec::new(),
 			controller_mode_stack: Vec::new(),
+			should_quit: false,
+
+			input_rx: None,
+			player_command_promise: None,
+
+			catalog,
 		}
 	}
 
@@ -26,6 +47,48 @@ impl TextView {
 			.cloned()
 			.expect("Empty controller stack!")
 	}
+
+	/// Parses whatever the player has typed since the last frame, waiting up to a frame for them to type something.
+	/// Lines are only parsed once they're needed, since what they mean depends on the controller mode at the time
+	fn read_player_command(&mut self) {
+		if self.input_rx.is_none() {
+			let (input_tx, input_rx) = channel();
+			thread::spawn(move || input_thread(input_tx));
+			self.input_rx = Some(input_rx);
+		}
+
+		while let Some(promise) = self.player_command_promise.take() {
+			// Whatever was waiting gave up, e.g. because an enemy got impatient. Anything typed is left for next time
+			if promise.is_abandoned() {
+				break
+			}
+
+			let line = match self.input_rx.as_ref().unwrap().recv_timeout(Duration::from_millis(16)) {
+				Ok(line) => line,
+				Err(RecvTimeoutError::Timeout) => {
+					self.player_command_promise = Some(promise);
+					break
+				}
+
+				Err(RecvTimeoutError::Disconnected) => {
+					self.should_quit = true;
+					break
+				}
+			};
+
+			match parse_player_command(self.catalog, self.current_controller_mode(), &line) {
+				Some(command) => promise.fulfill(command),
+				None => {
+					if !line.is_empty() {
+						println!("what does '{}' mean??", line);
+					}
+
+					print_prompt();
+					self.player_command_promise = Some(promise);
+				}
+			}
+		}
+	}
 }
 
 impl View for TextView {
@@ -39,8 +102,10 @@ impl View for TextView {
 		for (cmd, promise) in commands {
 			match cmd {
 				ViewCommand::GetPlayerCommand => {
-					let command = get_player_command_sync(self.current_controller_mode());
-					promise.player_command().fulfill(command);
+					let prev = self.player_command_promise.replace(promise.player_command());
+					assert!(prev.map_or(true, |p| p.is_abandoned()), "Trying to queue two GetPlayerCommands");
+
+					print_prompt();
 				}
 
 				ViewCommand::ShowMap { whole_map } => {
@@ -53,61 +118,23 @@ impl View for TextView {
 					promise.void().fulfill(());
 				}
 
+				ViewCommand::ShowRoute { route } => {
+					print_route(gamestate, &route);
+					promise.void().fulfill(());
+				}
+
 				ViewCommand::ShowInventory => {
-					print_inventory(&gamestate.player.inventory);
+					print_inventory(&gamestate.player);
 					promise.void().fulfill(());
 				}
 
-				ViewCommand::GameCommand(event) => {
-					use crate::gamestate::HealthModifyReason;
-					use crate::item::Item;
-					use std::cmp::Ordering;
-
-					match event {
-						GameCommand::GivePlayerItem(item, _) => match item {
-							Item::Food => println!("You found food!"),
-							Item::Treasure => println!("You found treasure!"),
-							Item::Key => println!("You found a key!"),
-							Item::Map => {
-								// TODO: println!("You found another map. It may have some value");
-								// how do I find out if player already had a map?
-								println!("You found a map!");
-							}
-
-							Item::Potion => println!("You found a potion"),
-
-							Item::Equipment(e) => {
-								// TODO: a/an obvs doesn't work
-								println!("You found a {:?}", e);
-							}
-						}
-
-						GameCommand::ModifyPlayerHealth(n, reason) => match n.cmp(&0) {
-							Ordering::Greater => {
-								println!("You gained {} health", n);
-							}
-
-							Ordering::Less => match reason {
-								HealthModifyReason::Attack => {
-									println!("You lost {} health!", -n);
-									if gamestate.player.is_dead() {
-										println!("Unfortunately, the strike is fatal");
-									}
-								}
-
-								_ => {}
-							}
-
-							Ordering::Equal => {}
-						}
-
-						GameCommand::MovePlayer(dir) => {
-							println!("You move {}", dir);
-						}
-
-						_ => {}
-					}
+				ViewCommand::ShowGameOver { summary, score, rank } => {
+					print_game_summary(&summary, score, rank);
+					promise.void().fulfill(());
+				}
 
+				ViewCommand::GameEvent(event) => {
+					print_event(&event);
 					promise.void().fulfill(());
 				}
 
@@ -126,17 +153,186 @@ impl View for TextView {
 				}
 			}
 		}
+
+		if self.player_command_promise.is_some() {
+			self.read_player_command();
+		}
+	}
+
+	fn should_quit(&self) -> bool { self.should_quit }
+
+	fn on_replayed_player_command(&mut self, command: &PlayerCommand) {
+		println!("> {:?}", command);
+	}
+}
+
+/// Describes an event the way it would be told to the player. Shared with `GfxView`, which prints to the console too
+pub(super) fn print_event(event: &GameEvent) {
+	use crate::gamestate::HealthModifyReason;
+	use crate::item::Item;
+
+	match *event {
+		GameEvent::ItemGained { item, count } => {
+			println!("You found {}!", describe_items(item, count));
+		}
+
+		GameEvent::SurplusItemGained { item, count } => match item {
+			Item::Map => println!("You found another map. It may be worth something"),
+			_ => println!("You found {}, but you can't carry any more. It may be worth something", describe_items(item, count)),
+		}
+
+		GameEvent::ItemConsumed { item, count } => match item {
+			// Eating is already obvious from whatever it was eaten for
+			Item::Food => {}
+			Item::Key => println!("You use up {}", describe_items(item, count)),
+			_ => println!("You hand over {}", describe_items(item, count)),
+		}
+
+		GameEvent::SurplusItemConsumed { item, count } => {
+			println!("You hand over {} you had spare", describe_items(item, count));
+		}
+
+		GameEvent::PlayerHealed { amount, health } => {
+			println!("You gained {} health, and now have {}", amount, health);
+		}
+
+		GameEvent::PlayerDamaged { amount, reason, remaining, fatal } => match reason {
+			HealthModifyReason::Attack => {
+				println!("You lost {} health!", amount);
+				if fatal {
+					println!("Unfortunately, the strike is fatal");
+				} else {
+					println!("You have {} health left", remaining);
+				}
+			}
+
+			HealthModifyReason::Trap => {
+				println!("You lost {} health!", amount);
+				if fatal {
+					println!("The wound proves fatal");
+				} else {
+					println!("You have {} health left", remaining);
+				}
+			}
+
+			_ => {}
+		}
+
+		GameEvent::PotionDrunk { health } => {
+			println!("You drink a potion and your health doubles to {}", health);
+		}
+
+		GameEvent::PlayerStarved { hunger, fatal } => {
+			if fatal {
+				println!("You starve to death");
+			} else {
+				println!("You have run out of food! You can travel {} rooms", hunger);
+			}
+		}
+
+		GameEvent::RoomEntered { location: Location(x, y), direction, first_visit } => {
+			if first_visit {
+				println!("You move {} to {}, {}", direction, x, y);
+			} else {
+				println!("You move {}, back to {}, {}", direction, x, y);
+			}
+		}
+
+		GameEvent::PlayerTeleported { location: Location(x, y) } => {
+			println!("You find yourself somewhere else entirely, at {}, {}", x, y);
+		}
+
+		GameEvent::ItemDropped { item, count } => {
+			println!("You dropped {}!", describe_items(item, count));
+		}
+
+		GameEvent::ItemEquipped(e) => {
+			println!("You equip the {:?}", e);
+		}
+
+		GameEvent::ItemUnequipped(slot) => {
+			println!("You unequip your {}", slot);
+		}
+
+		GameEvent::EnemyDamaged { archetype, amount, remaining } => {
+			if amount == 0 {
+				println!("The {:?} shrugs it off", archetype);
+			} else if remaining > 0 {
+				println!("The {:?} takes {} damage, leaving it with {} health", archetype, amount, remaining);
+			}
+		}
+
+		GameEvent::EnemyKilled { archetype } => {
+			println!("The strike is fatal! The {:?} is defeated!", archetype);
+		}
+
+		GameEvent::DungeonEscaped => println!("You escape the dungeon. You win!"),
+
+		GameEvent::GameSaved => println!("Game saved"),
+		GameEvent::GameLoaded => println!("Game loaded"),
+		GameEvent::GameRestarted { seed } => println!("A new dungeon awaits. Seed: {}", seed),
+
+		_ => {}
+	}
+}
+
+/// e.g. "a key", "3 keys", or "5 food"
+fn describe_items(item: crate::item::Item, count: usize) -> String {
+	use crate::item::Item;
+
+	let name = match item {
+		Item::Equipment(e) => format!("{:?}", e),
+		_ => format!("{:?}", item).to_ascii_lowercase(),
+	};
+
+	match item {
+		Item::Food | Item::Treasure => format!("{} {}", count, name),
+		_ if count == 1 => format!("a {}", name),
+		_ => format!("{} {}s", count, name),
+	}
+}
+
+/// Shared with `GfxView`, which has no way to show this other than the console
+pub(super) fn print_game_summary(summary: &GameSummary, score: u32, rank: Option<usize>) {
+	use crate::gamestate::GameOutcome;
+
+	match summary.outcome {
+		GameOutcome::Victory => println!("==== victory ===="),
+		GameOutcome::Defeat => println!("=== game over ==="),
+	}
+
+	println!("Rooms explored: {}", summary.rooms_explored);
+	println!("Enemies killed: {} ({} bosses)", summary.enemies_killed, summary.bosses_killed);
+	println!("Treasure: {}", summary.treasure);
+	println!("Turns survived: {}", summary.turns);
+	println!("Score: {}", score);
+
+	match rank {
+		Some(1) => println!("New high score for seed {}!", summary.seed),
+		Some(rank) => println!("That's #{} on the high score table for seed {}", rank, summary.seed),
+		None => {}
 	}
 
-	fn should_quit(&self) -> bool { false }
+	println!("=================");
 }
 
 fn print_map(state: &GameState) {
 	println!("==== map ====");
-	println!("{}", util::render_map(&state, state.map.bounds()));
+	println!("{}", util::render_map(&state, state.map.bounds(), &[]));
 	println!("=============");
 }
 
+/// Shows the whole map with `route` picked out. Shared with `GfxView`, which prints to the console too
+pub(super) fn print_route(state: &GameState, route: &[Location]) {
+	println!("==== route ====");
+	println!("{}", util::render_map(&state, state.map.bounds(), route));
+	match route.len().saturating_sub(1) {
+		1 => println!("1 room to go"),
+		n => println!("{} rooms to go", n),
+	}
+	println!("===============");
+}
+
 fn print_local_area(state: &GameState) {
 	let bounds = state.map.iter()
 		.filter(|(loc, _)| loc.distance(state.player.location) < 2)
@@ -144,12 +340,23 @@ fn print_local_area(state: &GameState) {
 		.expand(1, 0);
 
 	println!("=============");
-	println!("{}", util::render_map(&state, bounds));
+	println!("{}", util::render_map(&state, bounds, &[]));
 	println!("=============");
 }
 
-fn print_inventory(inv: &Inventory) {
-	use crate::item::Item;
+fn print_inventory(player: &Player) {
+	use crate::item::{Item, EquipSlot};
+
+	let equipped_str = EquipSlot::iter_all()
+		.filter_map(|slot| player.equipped(slot).map(|e| format!("{:?} ({})", e, slot)))
+		.collect::<Vec<_>>()
+		.join(", ");
+
+	if !equipped_str.is_empty() {
+		println!("Equipped: {}", equipped_str);
+	}
+
+	let inv = &player.inventory;
 
 	let mut items_sorted = inv.iter_items()
 		.collect::<Vec<_>>();
@@ -182,54 +389,72 @@ fn print_inventory(inv: &Inventory) {
 		println!("Items: {}", items_str);
 	}
 
+	let mut surplus_sorted = inv.iter_surplus().collect::<Vec<_>>();
+	surplus_sorted.sort();
+	surplus_sorted.dedup();
+
+	let surplus_str = surplus_sorted.into_iter()
+		.map(|i| format!("{:?} (x{})", i, inv.count_surplus(i)))
+		.collect::<Vec<_>>()
+		.join(", ");
+
+	if !surplus_str.is_empty() {
+		println!("To sell: {}", surplus_str);
+	}
+
 	println!("Treasure: {}", inv.count(Item::Treasure));
 	println!("Food: {}", inv.count(Item::Food));
 }
 
-fn get_player_command_sync(controller_mode: ControllerMode) -> PlayerCommand {
-	use std::io::{Write, BufRead};
-	use ControllerMode::*;
+fn print_prompt() {
+	use std::io::Write;
+
+	print!("> ");
 
-	loop {
-		print!("> ");
+	std::io::stdout().flush()
+		.expect("Failed to flush");
+}
 
-		std::io::stdout().flush()
-			.expect("Failed to flush");
+/// Sends on every line typed until there's no more input to be had
+fn input_thread(tx: Sender<String>) {
+	use std::io::BufRead;
 
-		let mut command_str = std::io::stdin().lock()
-			.lines().next()
-			.expect("EOF")
-			.expect("Failed to read stdin");
+	for line in std::io::stdin().lock().lines() {
+		let line = line.expect("Failed to read stdin");
 
-		if command_str.is_empty() {
-			continue;
+		if tx.send(line).is_err() {
+			break
 		}
+	}
+}
 
-		command_str.make_ascii_lowercase();
+/// Parses a line of input the way it would be typed into the console in `controller_mode`
+pub(super) fn parse_player_command(catalog: &'static ItemCatalog, controller_mode: ControllerMode, command_str: &str) -> Option<PlayerCommand> {
+	use ControllerMode::*;
 
-		if command_str.starts_with("d ") {
-			let parts = command_str[2..]
-				.split_whitespace()
-				.map(str::to_owned)
-				.collect();
+	let command_str = command_str.trim().to_ascii_lowercase();
 
-			break PlayerCommand::Debug(parts)
-		}
+	if command_str.starts_with("d ") {
+		let parts = command_str[2..]
+			.split_whitespace()
+			.map(str::to_owned)
+			.collect();
 
-		if let Some(command) = match controller_mode {
-			Main => parse_main_player_command(&command_str),
-			Battle => parse_battle_player_command(&command_str),
-			Merchant => parse_merchant_player_command(&command_str),
-		} {
-			break command
-		}
+		return Some(PlayerCommand::Debug(parts))
+	}
 
-		println!("what does '{}' mean??", command_str);
+	match controller_mode {
+		Main => parse_main_player_command(catalog, &command_str),
+		Battle => parse_battle_player_command(&command_str),
+		Merchant => parse_merchant_player_command(catalog, &command_str),
+		Disarm => parse_disarm_player_command(&command_str),
+		Equip => parse_equip_player_command(&command_str),
+		GameOver => parse_game_over_player_command(&command_str),
 	}
 }
 
 
-fn parse_main_player_command(cmd: &str) -> Option<PlayerCommand> {
+fn parse_main_player_command(catalog: &'static ItemCatalog, cmd: &str) -> Option<PlayerCommand> {
 	use crate::controller::main::PlayerCommand::*;
 
 	let cmd = match cmd {
@@ -241,23 +466,45 @@ fn parse_main_player_command(cmd: &str) -> Option<PlayerCommand> {
 		"i" | "inv" | "inventory" => ShowInventory,
 
 		"heal" | "eat" => Heal,
+		"p" | "potion" | "drink" => UsePotion,
 		"use" | "interact" => Interact,
 
-		// "r" | "restart" => Some(Event::Restart),
+		"save" => Save,
+		"load" => Load,
+
+		"r" | "restart" => Restart,
 		"q" | "quit" => Quit,
-		_ => return None
+		_ => return parse_goto_command(cmd)
+			.or_else(|| parse_equipment_command(catalog, cmd))
+			.map(PlayerCommand::Main)
 	};
 
 
 	Some(PlayerCommand::Main(cmd))
 }
 
+/// `goto <x> <y>`, `goto exit` or `goto merchant`. Shared with `GfxView`'s console.
+/// The start room is at 0 0, with north and east counting up
+pub(super) fn parse_goto_command(cmd: &str) -> Option<crate::controller::main::PlayerCommand> {
+	use crate::controller::main::{PlayerCommand::GoTo, Destination};
+
+	let destination = match cmd.split_whitespace().collect::<Vec<_>>()[..] {
+		["goto", "exit"] => Destination::Exit,
+		["goto", "merchant"] => Destination::Merchant,
+		["goto", x, y] => Destination::Location(Location(x.parse().ok()?, y.parse().ok()?)),
+		_ => return None
+	};
+
+	Some(GoTo(destination))
+}
+
 fn parse_battle_player_command(cmd: &str) -> Option<PlayerCommand> {
 	use crate::controller::battle::PlayerCommand::*;
 
 	let cmd = match cmd {
 		"f" | "fight" => Attack,
 		"e" | "eat" | "h" | "heal" => Heal,
+		"p" | "potion" | "drink" => UsePotion,
 		"r" | "run" | "flee" => Flee,
 		_ => return None
 	};
@@ -265,25 +512,75 @@ fn parse_battle_player_command(cmd: &str) -> Option<PlayerCommand> {
 	Some(PlayerCommand::Battle(cmd))
 }
 
-fn parse_merchant_player_command(cmd: &str) -> Option<PlayerCommand> {
+fn parse_merchant_player_command(catalog: &'static ItemCatalog, cmd: &str) -> Option<PlayerCommand> {
 	use crate::controller::merchant::PlayerCommand::*;
-	use crate::item::Item;
 
-	let cmd = match cmd {
-		"b food" => BuyItem(Item::Food),
-		"b map" => BuyItem(Item::Map),
-		"b key" => BuyItem(Item::Key),
-		"b equipment" => BuyItem(Item::Equipment(random())),
+	let cmd = match cmd.split_whitespace().collect::<Vec<_>>()[..] {
+		["b", "equipment"] => BuyAnyEquipment,
+		["b", name] => BuyItem(catalog.find(name)?),
+		["s", name] => SellItem(catalog.find(name)?),
+
+		["l"] | ["leave"] => Leave,
+
+		_ => return None
+	};
+
+
+	Some(PlayerCommand::Merchant(cmd))
+}
 
-		"s food" => SellItem(Item::Food),
-		"s map" => SellItem(Item::Map),
-		"s key" => SellItem(Item::Key),
+fn parse_disarm_player_command(cmd: &str) -> Option<PlayerCommand> {
+	use crate::controller::disarm::PlayerCommand::*;
 
+	let cmd = match cmd {
+		"a" | "attempt" | "disarm" => Attempt,
+		"i" | "inspect" => Inspect,
 		"l" | "leave" => Leave,
+		_ => return None
+	};
+
+	Some(PlayerCommand::Disarm(cmd))
+}
 
+fn parse_equip_player_command(cmd: &str) -> Option<PlayerCommand> {
+	use crate::controller::equip::PlayerCommand::*;
+
+	let cmd = match cmd {
+		"y" | "yes" | "swap" | "equip" => Swap,
+		"n" | "no" | "keep" => Keep,
 		_ => return None
 	};
 
+	Some(PlayerCommand::Equip(cmd))
+}
 
-	Some(PlayerCommand::Merchant(cmd))
-}
\ No newline at end of file
+fn parse_game_over_player_command(cmd: &str) -> Option<PlayerCommand> {
+	use crate::controller::game_over::PlayerCommand::*;
+
+	let cmd = match cmd {
+		"r" | "restart" => Restart,
+		"q" | "quit" => Quit,
+		_ => return None
+	};
+
+	Some(PlayerCommand::GameOver(cmd))
+}
+
+/// Parses `equip <equipment>` and `unequip <slot or equipment>`. Shared with `GfxView`'s console, since there are no buttons for these
+pub(super) fn parse_equipment_command(catalog: &'static ItemCatalog, cmd: &str) -> Option<crate::controller::main::PlayerCommand> {
+	use crate::controller::main::PlayerCommand::*;
+	use crate::item::EquipSlot;
+
+	let cmd = match cmd.split_whitespace().collect::<Vec<_>>()[..] {
+		["equip", name] => Equip(catalog.find_equipment(name)?),
+
+		["unequip", "weapon"] => Unequip(EquipSlot::Weapon),
+		["unequip", "armour"] | ["unequip", "armor"] => Unequip(EquipSlot::Armour),
+		["unequip", "offhand"] => Unequip(EquipSlot::Offhand),
+		["unequip", name] => UnequipItem(catalog.find_equipment(name)?),
+
+		_ => return None
+	};
+
+	Some(cmd)
+}
@@ -15,8 +15,11 @@ const EMPTY_CHAR: char = ' ';
 // const PLAYER_CHAR: char = '⚉'; // '\u{2689}';
 const PLAYER_CHAR: char = '⚲'; // '\u{26B2}';
 
+const ROUTE_CORRIDOR: [char; 4] = ['║', '═', '║', '═'];
 
-pub fn render_map(state: &GameState, bounds: Bounds) -> RenderBuffer {
+
+/// Any corridors along `route` are drawn doubled up, so the route stands out
+pub fn render_map(state: &GameState, bounds: Bounds, route: &[Location]) -> RenderBuffer {
 	let (width, height) = bounds.size();
 
 	assert!(width > 0 && height > 0, "Map is empty!");
@@ -54,6 +57,17 @@ pub fn render_map(state: &GameState, bounds: Bounds) -> RenderBuffer {
 		}
 	}
 
+	for step in route.windows(2) {
+		let (from, to) = (step[0], step[1]);
+		if !bounds.contains(from) || !bounds.contains(to) {
+			continue
+		}
+
+		if let Some(dir) = from.direction_to(to) {
+			buffer.write(room_to_buffer_space(from).offset_in_direction(dir), ROUTE_CORRIDOR[dir as usize]);
+		}
+	}
+
 	buffer.write(room_to_buffer_space(state.player.location), PLAYER_CHAR);
 
 	buffer